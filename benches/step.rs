@@ -0,0 +1,74 @@
+use chip8::interpreter::ChipState;
+use chip8_base::Interpreter;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+/// Adds and shifts registers in a tight loop without ever drawing.
+const COMPUTE_ROM: [u8; 12] = [
+    0x60, 0x01, // 0x200: LD V0, 0x01
+    0x71, 0x03, // 0x202: ADD V1, 0x03
+    0x80, 0x14, // 0x204: ADD V0, V1
+    0x82, 0x06, // 0x206: SHR V2, V0
+    0x83, 0x23, // 0x208: XOR V3, V2
+    0x12, 0x02, // 0x20A: JP 0x202
+];
+
+/// Draws the digit in V0 over itself forever, so every other step changes the display.
+const DRAW_ROM: [u8; 6] = [
+    0xF0, 0x29, // 0x200: LD F, V0
+    0xD0, 0x05, // 0x202: DRW V0, V0, 5
+    0x12, 0x02, // 0x204: JP 0x202
+];
+
+const STEPS: usize = 10_000;
+
+fn chip(rom: &[u8]) -> ChipState {
+    let mut chip = ChipState::new(700);
+    chip.load_bytes(rom, 0x200).unwrap();
+    chip
+}
+
+fn bench_step(c: &mut Criterion) {
+    let keys = [false; 16];
+
+    c.bench_function("step_compute", |b| {
+        b.iter_batched(
+            || chip(&COMPUTE_ROM),
+            |mut chip| {
+                for _ in 0..STEPS {
+                    chip.step_compute(black_box(&keys));
+                }
+                chip
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("step_draw", |b| {
+        b.iter_batched(
+            || chip(&DRAW_ROM),
+            |mut chip| {
+                for _ in 0..STEPS {
+                    black_box(chip.step(black_box(&keys)));
+                }
+                chip
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("step_compute_draw", |b| {
+        b.iter_batched(
+            || chip(&DRAW_ROM),
+            |mut chip| {
+                for _ in 0..STEPS {
+                    chip.step_compute(black_box(&keys));
+                }
+                chip
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_step);
+criterion_main!(benches);
@@ -0,0 +1,141 @@
+use crate::interpreter::{DecodeError, Instruction};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Disassembles `bytes` as if loaded at `start_addr`, returning the address, opcode and
+/// mnemonic of each word. Words that don't decode are rendered as `DB` data.
+pub fn disassemble(bytes: &[u8], start_addr: u16) -> Vec<(u16, u16, String)> {
+    decode_all(bytes, start_addr)
+        .map(|(addr, opcode, result)| {
+            let text = match result {
+                Ok(instruction) => instruction.to_string(),
+                // A trailing odd byte can't form an opcode
+                Err(_) if addr.wrapping_sub(start_addr) as usize + 1 == bytes.len() => {
+                    format!("DB {:#04X}", opcode)
+                }
+                Err(_) => format!("DB {:#06X}", opcode),
+            };
+            (addr, opcode, text)
+        })
+        .collect()
+}
+
+/// Decodes `bytes` as if loaded at `start_addr`, yielding the address and instruction of
+/// each word that decodes. Words that don't, and a trailing odd byte, are skipped.
+pub fn instructions(
+    bytes: &[u8],
+    start_addr: u16,
+) -> impl Iterator<Item = (u16, Instruction)> + '_ {
+    decode_all(bytes, start_addr).filter_map(|(addr, _, result)| Some((addr, result.ok()?)))
+}
+
+/// Decodes every word of `bytes` as if loaded at `start_addr`, yielding the address, the
+/// opcode and the result of decoding it. A trailing odd byte is reported as an error.
+fn decode_all(
+    bytes: &[u8],
+    start_addr: u16,
+) -> impl Iterator<Item = (u16, u16, Result<Instruction, DecodeError>)> + '_ {
+    let mut offset = 0;
+
+    core::iter::from_fn(move || {
+        let addr = start_addr.wrapping_add(offset as u16);
+        if offset + 1 >= bytes.len() {
+            let byte = *bytes.get(offset)?;
+            offset += 1;
+            return Some((
+                addr,
+                byte as u16,
+                Err(DecodeError {
+                    opcode: byte as u16,
+                    pc: addr,
+                }),
+            ));
+        }
+
+        let opcode = u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+        offset += 2;
+
+        let result = match Instruction::decode(opcode, addr) {
+            // F000 NNNN is the only instruction wider than one word (XO-CHIP)
+            Ok(Instruction::LoadILong(_)) if offset + 1 < bytes.len() => {
+                let long = u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+                offset += 2;
+                Ok(Instruction::LoadILong(long))
+            }
+            result => result,
+        };
+        Some((addr, opcode, result))
+    })
+}
+
+/// How often each instruction appears in a ROM, from `analyze`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OpcodeStats {
+    /// Occurrences of each instruction, keyed by `Instruction::name`
+    pub counts: BTreeMap<&'static str, usize>,
+    /// Words that don't decode to any instruction
+    pub illegal: usize,
+}
+
+/// Counts the instructions in `bytes`, decoding it linearly from the start.
+///
+/// Like `disassemble`, this can't tell code from data: sprites and other data are counted
+/// as whatever instructions they happen to decode to, and code at odd offsets is missed.
+pub fn analyze(bytes: &[u8]) -> OpcodeStats {
+    let mut stats = OpcodeStats::default();
+
+    for (_, _, result) in decode_all(bytes, 0x200) {
+        match result {
+            Ok(instruction) => *stats.counts.entry(instruction.name()).or_insert(0) += 1,
+            Err(_) => stats.illegal += 1,
+        }
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyze_counts_a_known_mix() {
+        // Two loads, a draw, an illegal 5xy1, F000 NNNN, a jump and a trailing odd byte
+        let rom = [
+            0x60, 0x01, 0x61, 0x02, 0xD0, 0x15, 0x5A, 0xB1, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x00,
+            0xFF,
+        ];
+        let stats = analyze(&rom);
+        assert_eq!(stats.counts["Setr"], 2);
+        assert_eq!(stats.counts["Draw"], 1);
+        assert_eq!(stats.counts["LoadILong"], 1);
+        assert_eq!(stats.counts["Jmp"], 1);
+        assert_eq!(stats.counts.values().sum::<usize>(), 5);
+        assert_eq!(stats.illegal, 2);
+        assert_eq!(analyze(&[]), OpcodeStats::default());
+    }
+
+    #[test]
+    fn disassembles_a_known_rom() {
+        // A load, a draw, an illegal 5xy1, F000 NNNN and a trailing odd byte
+        let rom = [
+            0x60, 0x05, 0xD1, 0x25, 0x5A, 0xB1, 0xF0, 0x00, 0x12, 0x34, 0xFF,
+        ];
+        let lines: Vec<String> = disassemble(&rom, 0x200)
+            .iter()
+            .map(|(addr, opcode, text)| format!("{:03X} {:04X} {}", addr, opcode, text))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "200 6005 LD V0, 0x05",
+                "202 D125 DRW V1, V2, 5",
+                "204 5AB1 DB 0x5AB1",
+                "206 F000 LD I, long 0x1234",
+                "20A 00FF DB 0xFF",
+            ]
+        );
+    }
+}
@@ -0,0 +1,32 @@
+/// A rectangle of the display, in active-resolution pixels, that changed since it was last taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayDiff {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DisplayDiff {
+    pub(super) fn pixel(x: usize, y: usize) -> Self {
+        DisplayDiff {
+            x,
+            y,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    /// Grows the region to cover `other` as well.
+    pub(super) fn union(self, other: DisplayDiff) -> Self {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        DisplayDiff {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
@@ -0,0 +1,27 @@
+/// How each 2x2 block of the hires display collapses to one pixel of the 64x32 frame
+/// handed to chip8_base. Collisions always use the full hires display.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DownscaleMode {
+    /// Take the top-left pixel of each block
+    Nearest,
+    /// Take the brightest colour in each block, so no lit pixel is lost
+    #[default]
+    OrSample,
+    /// Light each plane where at least half of the block is lit on it
+    AverageThreshold,
+}
+
+impl DownscaleMode {
+    /// Collapses the colours of a 2x2 block, in row order, to one colour. Bit 0 of a colour
+    /// is plane 0 and bit 1 is plane 1.
+    pub(super) fn collapse(self, block: [u8; 4]) -> u8 {
+        match self {
+            DownscaleMode::Nearest => block[0],
+            DownscaleMode::OrSample => block.into_iter().max().unwrap_or(0),
+            DownscaleMode::AverageThreshold => (0..2).fold(0, |colour, plane| {
+                let lit = block.iter().filter(|&&c| c >> plane & 0b1 == 1).count();
+                colour | ((lit >= 2) as u8) << plane
+            }),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use alloc::string::String;
+use core::{error, fmt};
+#[cfg(feature = "std")]
+use std::io;
+
+/// Everything that can go wrong loading or running a ROM.
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// A ROM could not be read
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// `ChipState::load_embedded` was given a name not in `crate::roms`
+    UnknownRom(String),
+    /// A ROM had no bytes in it
+    EmptyRom,
+    /// A ROM was too big to fit in memory after its load address
+    RomTooLarge { size: usize, capacity: usize },
+    /// An opcode did not match any known instruction
+    IllegalOpcode(DecodeError),
+    /// A Call was made with every stack slot already in use
+    StackOverflow { pc: u16 },
+    /// A Ret was made with an empty stack
+    StackUnderflow { pc: u16 },
+    /// An address was outside of the addressable memory
+    MemoryOutOfBounds { addr: usize },
+    /// Fx33, Fx55 or 5xy2 at `pc` wrote to `addr`, in code that has already run
+    CodeWrite { pc: u16, addr: u16 },
+    /// A custom font at `offset` would run past the interpreter area into 0x200
+    FontOutOfRange { offset: u16 },
+    /// `ChipState::load_state` was given bytes it couldn't restore
+    InvalidSaveState(&'static str),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Chip8Error::Io(e) => write!(f, "{}", e),
+            Chip8Error::UnknownRom(name) => write!(f, "No built-in ROM named {}", name),
+            Chip8Error::EmptyRom => write!(f, "ROM is empty"),
+            Chip8Error::RomTooLarge { size, capacity } => write!(
+                f,
+                "ROM is {} bytes but only {} bytes are available",
+                size, capacity
+            ),
+            Chip8Error::IllegalOpcode(e) => write!(f, "{}", e),
+            Chip8Error::StackOverflow { pc } => write!(f, "Stack overflow at {:#05X}", pc),
+            Chip8Error::StackUnderflow { pc } => write!(f, "Stack underflow at {:#05X}", pc),
+            Chip8Error::MemoryOutOfBounds { addr } => {
+                write!(f, "Address {:#06X} is outside of memory", addr)
+            }
+            Chip8Error::CodeWrite { pc, addr } => write!(
+                f,
+                "Write at {:#05X} modifies code at {:#05X} that has already run",
+                pc, addr
+            ),
+            Chip8Error::FontOutOfRange { offset } => {
+                write!(f, "Font at {:#05X} does not fit below 0x200", offset)
+            }
+            Chip8Error::InvalidSaveState(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl error::Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Chip8Error::Io(e) => Some(e),
+            Chip8Error::IllegalOpcode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Chip8Error {
+    fn from(e: io::Error) -> Self {
+        Chip8Error::Io(e)
+    }
+}
+
+impl From<DecodeError> for Chip8Error {
+    fn from(e: DecodeError) -> Self {
+        Chip8Error::IllegalOpcode(e)
+    }
+}
+
+/// An opcode that could not be decoded, along with the address it was fetched from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+    pub pc: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unsupported instruction {:#06X} at {:#05X}",
+            self.opcode, self.pc
+        )
+    }
+}
+
+impl error::Error for DecodeError {}
+
+/// What the interpreter does when it fetches an opcode it cannot decode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecodePolicy {
+    /// Log the opcode and stop executing
+    #[default]
+    Halt,
+    /// Log the opcode and carry on as if it were a Nop
+    Nop,
+}
+
+/// What the interpreter does with a 0nnn SYS call to a machine code routine, which it
+/// can't run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SysPolicy {
+    /// Skip it as a Nop, like modern interpreters
+    #[default]
+    Ignore,
+    /// Log the address and record it for `ChipState::last_sys`, then carry on
+    Trap,
+    /// Record the address like `Trap` and stop executing, leaving the pc on the call
+    Halt,
+}
+
+/// What the interpreter does when Fx33, Fx55 or 5xy2 writes over code that has already
+/// run, which is usually a bug but some ROMs do on purpose.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CodeWritePolicy {
+    /// Allow the write without checking
+    #[default]
+    Allow,
+    /// Log the write and record it for `ChipState::last_code_write`, then carry on
+    Warn,
+    /// Stop executing with `Chip8Error::CodeWrite`
+    Error,
+}
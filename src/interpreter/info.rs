@@ -0,0 +1,21 @@
+/// Metadata about the loaded ROM, from `ChipState::rom_info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomInfo {
+    /// Length of the ROM in bytes
+    pub len: usize,
+    /// CRC-32 of the ROM as loaded, for matching it against a database of known ROMs
+    pub crc32: u32,
+    /// Address the ROM was loaded at
+    pub start: u16,
+    /// Last address the ROM occupies
+    pub end: u16,
+}
+
+/// The CRC-32 (IEEE 802.3, as used by zip and PNG) of `bytes`.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
@@ -0,0 +1,22 @@
+/// What registers and memory outside the fonts and ROM are filled with by
+/// `ChipState::set_memory_init`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MemoryInit {
+    /// Everything starts cleared to 0
+    #[default]
+    Zero,
+    /// Every byte is set to the given value, so reads of uninitialised state stand out
+    Pattern(u8),
+    /// Every byte is random, drawn from the interpreter's random number generator
+    Random,
+}
+
+/// How much of the machine `ChipState::reset_with` clears.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResetPolicy {
+    /// Clear the volatile state but keep the SUPER-CHIP flags, as the HP-48 did
+    #[default]
+    Soft,
+    /// Clear the SUPER-CHIP flags too
+    Hard,
+}
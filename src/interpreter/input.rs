@@ -0,0 +1,56 @@
+use chip8_base::Keys;
+
+/// The state of the hex keypad as a host builds it up from individual key events, before
+/// handing a snapshot of it to `Interpreter::step`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InputState {
+    keys: Keys,
+}
+
+impl InputState {
+    /// A keypad with every key released.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Holds `key` down. Returns false, changing nothing, if `key` isn't a hex digit.
+    pub fn press(&mut self, key: u8) -> bool {
+        self.set(key, true)
+    }
+
+    /// Lets `key` go. Returns false, changing nothing, if `key` isn't a hex digit.
+    pub fn release(&mut self, key: u8) -> bool {
+        self.set(key, false)
+    }
+
+    /// Lets every key go.
+    pub fn release_all(&mut self) {
+        self.keys = [false; 16];
+    }
+
+    /// Whether `key` is held down. Keys that aren't hex digits never are.
+    pub fn is_down(&self, key: u8) -> bool {
+        self.keys.get(key as usize).copied().unwrap_or(false)
+    }
+
+    /// The keys currently held down, in the layout `Interpreter::step` takes.
+    pub fn snapshot(&self) -> Keys {
+        self.keys
+    }
+
+    fn set(&mut self, key: u8, down: bool) -> bool {
+        match self.keys.get_mut(key as usize) {
+            Some(state) => {
+                *state = down;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl From<Keys> for InputState {
+    fn from(keys: Keys) -> Self {
+        InputState { keys }
+    }
+}
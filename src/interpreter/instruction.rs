@@ -1,43 +1,60 @@
+use super::DecodeError;
+use core::fmt;
+
 type Addr = u16;
 type Reg = u8;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
-    Nop,                // 0nnn (SYS addr) - Ignored by modern interpreters
-    Cls,                // 00E0 (CLS) - Clears the display
-    Ret,                // 00EE (RET) - Returns from the subroutine (PC set to top of stack)
-    Jmp(Addr),          // 1nnn (JP addr) - Jump to location (Sets PC to nnn/addr)
-    Call(Addr),         // 2nnn (CALL addr) - Call subroutine at nnn/addr
-    Ske(Reg, u8),       // 3xkk (SE Vx, byte) - Skip next instruction if Vx == kk
-    Skne(Reg, u8),      // 4xkk (SNE Vx, byte) - Skip next instruction if Vx != kk
-    Skre(Reg, Reg),     // 5xy0 (SE Vx, Vy) - Skip next instruction if Vx == Vy
-    Setr(Reg, u8),      // 6xkk (LD Vx, byte) - Set register Vx to kk
-    Addr(Reg, u8),      // 7xkk (ADD Vx, byte) - Set Vx = Vx + kk
-    Move(Reg, Reg),     // 8xy0 (LD Vx, Vy) - Stores the value of Vy in Vx
-    Or(Reg, Reg),       // 8xy1 (OR Vx, Vy) - Set Vx = Vx OR Vy
-    And(Reg, Reg),      // 8xy2 (AND Vx, Vy) - Set Vx = Vx AND Vy
-    Xor(Reg, Reg),      // 8xy3 (XOR Vx, Vy) - Set Vx = Vx XOR Vy
-    Add(Reg, Reg),      // 8xy4 (ADD Vx, Vy) - Set Vx = Vx + Vy (and VF = carry)
-    Sub(Reg, Reg),      // 8xy5 (SUB Vx, Vy) - Set Vx = Vx - Vy (if Vx > Vy, VF = 1)
-    Shr(Reg, Reg),      // 8xy6 (SHR Vx, Vy) - Set Vx = Vy >> 1
-    Ssub(Reg, Reg),     // 8xy7 (SUBN Vx, Vy) - Set Vx = Vy - Vx (if Vy > Vx, VF = 1)
-    Shl(Reg, Reg),      // 8xyE (SHL Vx, Vy) - Set Vx = Vy << 1
-    Skrne(Reg, Reg),    // 9xy0 (SNE Vx, Vy) - Skip next instruction if Vx != Vy
-    Seti(Addr),         // Annn (LD I, addr) - Set index register to nnn/addr
-    Jmpr(Addr),         // Bnnn (JP V0, addr) - Jump to location nnn/addr + V0
-    Rand(Reg, u8),      // Cxkk (RND Vx, byte) - Set Vx = random byte AND kk
+    Nop,                 // 0nnn (SYS addr) - Ignored by modern interpreters
+    Cls,                 // 00E0 (CLS) - Clears the display
+    ScrollDown(u8),      // 00Cn (SCD nibble) - Scroll the display down n pixels (SUPER-CHIP)
+    Ret,                 // 00EE (RET) - Returns from the subroutine (PC set to top of stack)
+    ScrollRight,         // 00FB (SCR) - Scroll the display right 4 pixels (SUPER-CHIP)
+    ScrollLeft,          // 00FC (SCL) - Scroll the display left 4 pixels (SUPER-CHIP)
+    Lores,               // 00FE (LOW) - Switch to 64x32 low resolution mode (SUPER-CHIP)
+    Hires,               // 00FF (HIGH) - Switch to 128x64 high resolution mode (SUPER-CHIP)
+    Jmp(Addr),           // 1nnn (JP addr) - Jump to location (Sets PC to nnn/addr)
+    Call(Addr),          // 2nnn (CALL addr) - Call subroutine at nnn/addr
+    Ske(Reg, u8),        // 3xkk (SE Vx, byte) - Skip next instruction if Vx == kk
+    Skne(Reg, u8),       // 4xkk (SNE Vx, byte) - Skip next instruction if Vx != kk
+    Skre(Reg, Reg),      // 5xy0 (SE Vx, Vy) - Skip next instruction if Vx == Vy
+    SaveRange(Reg, Reg), // 5xy2 (SAVE Vx - Vy) - Store regs Vx to Vy in memory at I (XO-CHIP)
+    LoadRange(Reg, Reg), // 5xy3 (LOAD Vx - Vy) - Read regs Vx to Vy from memory at I (XO-CHIP)
+    Setr(Reg, u8),       // 6xkk (LD Vx, byte) - Set register Vx to kk
+    Addr(Reg, u8),       // 7xkk (ADD Vx, byte) - Set Vx = Vx + kk
+    Move(Reg, Reg),      // 8xy0 (LD Vx, Vy) - Stores the value of Vy in Vx
+    Or(Reg, Reg),        // 8xy1 (OR Vx, Vy) - Set Vx = Vx OR Vy
+    And(Reg, Reg),       // 8xy2 (AND Vx, Vy) - Set Vx = Vx AND Vy
+    Xor(Reg, Reg),       // 8xy3 (XOR Vx, Vy) - Set Vx = Vx XOR Vy
+    Add(Reg, Reg),       // 8xy4 (ADD Vx, Vy) - Set Vx = Vx + Vy (and VF = carry)
+    Sub(Reg, Reg),       // 8xy5 (SUB Vx, Vy) - Set Vx = Vx - Vy (if Vx > Vy, VF = 1)
+    Shr(Reg, Reg),       // 8xy6 (SHR Vx, Vy) - Set Vx = Vy >> 1
+    Ssub(Reg, Reg),      // 8xy7 (SUBN Vx, Vy) - Set Vx = Vy - Vx (if Vy > Vx, VF = 1)
+    Shl(Reg, Reg),       // 8xyE (SHL Vx, Vy) - Set Vx = Vy << 1
+    Skrne(Reg, Reg),     // 9xy0 (SNE Vx, Vy) - Skip next instruction if Vx != Vy
+    Seti(Addr),          // Annn (LD I, addr) - Set index register to nnn/addr
+    Jmpr(Reg, Addr),     // Bnnn (JP V0, addr) - Jump to location nnn/addr + V0 (or Bxnn, + Vx)
+    Rand(Reg, u8),       // Cxkk (RND Vx, byte) - Set Vx = random byte AND kk
     Draw(Reg, Reg, u8), // Dxyn (DRW Vx, Vy, nibble) - Display n-byte sprite starting at location I at co-ord (Vx, Vy) (VF = collision)
     Skp(Reg),           // Ex9E (SKP Vx) - Skip next instruction if key with value of Vx down
     Sknp(Reg),          // ExA1 (SKNP Vx) - Skip next instruction if key with value of Vx not down
+    LoadILong(Addr),    // F000 nnnn (LD I, long nnnn) - Set index register to 16-bit nnnn (XO-CHIP)
+    StoreAudio,         // F002 (AUDIO) - Load the 16-byte audio pattern from location I (XO-CHIP)
+    Plane(u8),          // Fn01 (PLANE n) - Select bit planes for Draw, Cls and scrolling (XO-CHIP)
     Moved(Reg),         // Fx07 (LD Vx, DT) - Set Vx to delay timer
     Key(Reg),           // Fx0A (LD Vx, K) - Wait for a key press, store key value in Vx
     Setrd(Reg),         // Fx15 (LD DT, Vx) - Set delay timer to Vx
     Setrs(Reg),         // Fx18 (LD ST, Vx) - Set sound timer to Vx
     Addi(Reg),          // Fx1E (ADD I, Vx) - Add Vx to value of index register
     Ldfnt(Reg),         // Fx29 (LD F, Vx) - Set index register to location of sprite for digit Vx
+    Ldbigfnt(Reg),      // Fx30 (LD HF, Vx) - Set index register to location of 8x10 sprite for Vx
+    SetPitch(Reg),      // Fx3A (PITCH Vx) - Set the audio pattern playback pitch to Vx (XO-CHIP)
     Bcd(Reg),           // Fx33 (LD B, Vx) - Store BCD representation of Vx in locations I..=I+2
     Store(Reg),         // Fx55 (LD [I], Vx) - Store regs V0 to Vx in memory starting at location I
     Load(Reg),          // Fx65 (LD Vx, [I]) - Read regs V0 to Vx from memory starting at location I
+    StoreFlags(Reg),    // Fx75 (LD R, Vx) - Store regs V0 to Vx (x <= 7) in the flags (SUPER-CHIP)
+    LoadFlags(Reg),     // Fx85 (LD Vx, R) - Read regs V0 to Vx (x <= 7) from the flags (SUPER-CHIP)
 }
 
 fn nibbles(n: u16) -> (u8, u8, u8, u8) {
@@ -50,19 +67,27 @@ fn nibbles(n: u16) -> (u8, u8, u8, u8) {
 
 use Instruction::*;
 impl Instruction {
-    pub fn decode(opcode: u16) -> Self {
+    /// Decodes `opcode`, which was fetched from address `pc`.
+    pub fn decode(opcode: u16, pc: u16) -> Result<Self, DecodeError> {
         let addr = opcode & 0x0FFF;
         let byte = (opcode & 0x00FF) as u8;
 
-        match nibbles(opcode) {
+        let instruction = match nibbles(opcode) {
             (0x0, 0x0, 0xE, 0xE) => Ret,
+            (0x0, 0x0, 0xC, n) => ScrollDown(n),
             (0x0, 0x0, 0xE, 0x0) => Cls,
+            (0x0, 0x0, 0xF, 0xB) => ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) => ScrollLeft,
+            (0x0, 0x0, 0xF, 0xE) => Lores,
+            (0x0, 0x0, 0xF, 0xF) => Hires,
             (0x0, _, _, _) => Nop,
             (0x1, _, _, _) => Jmp(addr),
             (0x2, _, _, _) => Call(addr),
             (0x3, x, _, _) => Ske(x, byte),
             (0x4, x, _, _) => Skne(x, byte),
             (0x5, x, y, 0x0) => Skre(x, y),
+            (0x5, x, y, 0x2) => SaveRange(x, y),
+            (0x5, x, y, 0x3) => LoadRange(x, y),
             (0x6, x, _, _) => Setr(x, byte),
             (0x7, x, _, _) => Addr(x, byte),
             (0x8, x, y, 0x0) => Move(x, y),
@@ -76,21 +101,203 @@ impl Instruction {
             (0x8, x, y, 0xE) => Shl(x, y),
             (0x9, x, y, 0x0) => Skrne(x, y),
             (0xA, _, _, _) => Seti(addr),
-            (0xB, _, _, _) => Jmpr(addr),
+            (0xB, x, _, _) => Jmpr(x, addr),
             (0xC, x, _, _) => Rand(x, byte),
             (0xD, x, y, n) => Draw(x, y, n),
             (0xE, x, 0x9, 0xE) => Skp(x),
             (0xE, x, 0xA, 0x1) => Sknp(x),
+            // The address is the word after the opcode, which the caller fetches
+            (0xF, 0x0, 0x0, 0x0) => LoadILong(0),
+            (0xF, n, 0x0, 0x1) => Plane(n),
+            (0xF, 0x0, 0x0, 0x2) => StoreAudio,
             (0xF, x, 0x0, 0x7) => Moved(x),
             (0xF, x, 0x0, 0xA) => Key(x),
             (0xF, x, 0x1, 0x5) => Setrd(x),
             (0xF, x, 0x1, 0x8) => Setrs(x),
             (0xF, x, 0x1, 0xE) => Addi(x),
             (0xF, x, 0x2, 0x9) => Ldfnt(x),
+            (0xF, x, 0x3, 0x0) => Ldbigfnt(x),
+            (0xF, x, 0x3, 0xA) => SetPitch(x),
             (0xF, x, 0x3, 0x3) => Bcd(x),
             (0xF, x, 0x5, 0x5) => Store(x),
             (0xF, x, 0x6, 0x5) => Load(x),
-            _ => panic!("Unsupported instruction found: {:#06X}", opcode),
+            (0xF, x, 0x7, 0x5) => StoreFlags(x),
+            (0xF, x, 0x8, 0x5) => LoadFlags(x),
+            _ => return Err(DecodeError { opcode, pc }),
+        };
+
+        Ok(instruction)
+    }
+
+    /// Encodes this instruction as an opcode, the inverse of `decode`.
+    ///
+    /// `Nop` encodes as 0000, and the x of `Jmpr` is taken from its address, as neither
+    /// is kept by `decode`. `LoadILong` only gives the F000 word; its address follows it.
+    pub fn encode(&self) -> u16 {
+        let xy = |x: Reg, y: Reg| (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4;
+        let xkk = |x: Reg, byte: u8| (x as u16 & 0xF) << 8 | byte as u16;
+
+        match *self {
+            Nop => 0x0000,
+            Cls => 0x00E0,
+            ScrollDown(n) => 0x00C0 | (n as u16 & 0xF),
+            Ret => 0x00EE,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Lores => 0x00FE,
+            Hires => 0x00FF,
+            Jmp(addr) => 0x1000 | (addr & 0x0FFF),
+            Call(addr) => 0x2000 | (addr & 0x0FFF),
+            Ske(x, byte) => 0x3000 | xkk(x, byte),
+            Skne(x, byte) => 0x4000 | xkk(x, byte),
+            Skre(x, y) => 0x5000 | xy(x, y),
+            SaveRange(x, y) => 0x5002 | xy(x, y),
+            LoadRange(x, y) => 0x5003 | xy(x, y),
+            Setr(x, byte) => 0x6000 | xkk(x, byte),
+            Addr(x, byte) => 0x7000 | xkk(x, byte),
+            Move(x, y) => 0x8000 | xy(x, y),
+            Or(x, y) => 0x8001 | xy(x, y),
+            And(x, y) => 0x8002 | xy(x, y),
+            Xor(x, y) => 0x8003 | xy(x, y),
+            Add(x, y) => 0x8004 | xy(x, y),
+            Sub(x, y) => 0x8005 | xy(x, y),
+            Shr(x, y) => 0x8006 | xy(x, y),
+            Ssub(x, y) => 0x8007 | xy(x, y),
+            Shl(x, y) => 0x800E | xy(x, y),
+            Skrne(x, y) => 0x9000 | xy(x, y),
+            Seti(addr) => 0xA000 | (addr & 0x0FFF),
+            Jmpr(_, addr) => 0xB000 | (addr & 0x0FFF),
+            Rand(x, byte) => 0xC000 | xkk(x, byte),
+            Draw(x, y, n) => 0xD000 | xy(x, y) | (n as u16 & 0xF),
+            Skp(x) => 0xE09E | xy(x, 0),
+            Sknp(x) => 0xE0A1 | xy(x, 0),
+            LoadILong(_) => 0xF000,
+            StoreAudio => 0xF002,
+            Plane(n) => 0xF001 | xy(n, 0),
+            Moved(x) => 0xF007 | xy(x, 0),
+            Key(x) => 0xF00A | xy(x, 0),
+            Setrd(x) => 0xF015 | xy(x, 0),
+            Setrs(x) => 0xF018 | xy(x, 0),
+            Addi(x) => 0xF01E | xy(x, 0),
+            Ldfnt(x) => 0xF029 | xy(x, 0),
+            Ldbigfnt(x) => 0xF030 | xy(x, 0),
+            SetPitch(x) => 0xF03A | xy(x, 0),
+            Bcd(x) => 0xF033 | xy(x, 0),
+            Store(x) => 0xF055 | xy(x, 0),
+            Load(x) => 0xF065 | xy(x, 0),
+            StoreFlags(x) => 0xF075 | xy(x, 0),
+            LoadFlags(x) => 0xF085 | xy(x, 0),
+        }
+    }
+
+    /// The name of this instruction's variant, which stays the same whatever its operands.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Nop => "Nop",
+            Cls => "Cls",
+            ScrollDown(..) => "ScrollDown",
+            Ret => "Ret",
+            ScrollRight => "ScrollRight",
+            ScrollLeft => "ScrollLeft",
+            Lores => "Lores",
+            Hires => "Hires",
+            Jmp(..) => "Jmp",
+            Call(..) => "Call",
+            Ske(..) => "Ske",
+            Skne(..) => "Skne",
+            Skre(..) => "Skre",
+            SaveRange(..) => "SaveRange",
+            LoadRange(..) => "LoadRange",
+            Setr(..) => "Setr",
+            Addr(..) => "Addr",
+            Move(..) => "Move",
+            Or(..) => "Or",
+            And(..) => "And",
+            Xor(..) => "Xor",
+            Add(..) => "Add",
+            Sub(..) => "Sub",
+            Shr(..) => "Shr",
+            Ssub(..) => "Ssub",
+            Shl(..) => "Shl",
+            Skrne(..) => "Skrne",
+            Seti(..) => "Seti",
+            Jmpr(..) => "Jmpr",
+            Rand(..) => "Rand",
+            Draw(..) => "Draw",
+            Skp(..) => "Skp",
+            Sknp(..) => "Sknp",
+            LoadILong(..) => "LoadILong",
+            StoreAudio => "StoreAudio",
+            Plane(..) => "Plane",
+            Moved(..) => "Moved",
+            Key(..) => "Key",
+            Setrd(..) => "Setrd",
+            Setrs(..) => "Setrs",
+            Addi(..) => "Addi",
+            Ldfnt(..) => "Ldfnt",
+            Ldbigfnt(..) => "Ldbigfnt",
+            SetPitch(..) => "SetPitch",
+            Bcd(..) => "Bcd",
+            Store(..) => "Store",
+            Load(..) => "Load",
+            StoreFlags(..) => "StoreFlags",
+            LoadFlags(..) => "LoadFlags",
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Nop => write!(f, "SYS"),
+            Cls => write!(f, "CLS"),
+            ScrollDown(n) => write!(f, "SCD {}", n),
+            Ret => write!(f, "RET"),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Lores => write!(f, "LOW"),
+            Hires => write!(f, "HIGH"),
+            Jmp(addr) => write!(f, "JP {:#05X}", addr),
+            Call(addr) => write!(f, "CALL {:#05X}", addr),
+            Ske(x, byte) => write!(f, "SE V{:X}, {:#04X}", x, byte),
+            Skne(x, byte) => write!(f, "SNE V{:X}, {:#04X}", x, byte),
+            Skre(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            SaveRange(x, y) => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            LoadRange(x, y) => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Setr(x, byte) => write!(f, "LD V{:X}, {:#04X}", x, byte),
+            Addr(x, byte) => write!(f, "ADD V{:X}, {:#04X}", x, byte),
+            Move(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Add(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Shr(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Ssub(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Shl(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Skrne(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Seti(addr) => write!(f, "LD I, {:#05X}", addr),
+            Jmpr(_, addr) => write!(f, "JP V0, {:#05X}", addr),
+            Rand(x, byte) => write!(f, "RND V{:X}, {:#04X}", x, byte),
+            Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Skp(x) => write!(f, "SKP V{:X}", x),
+            Sknp(x) => write!(f, "SKNP V{:X}", x),
+            LoadILong(addr) => write!(f, "LD I, long {:#06X}", addr),
+            StoreAudio => write!(f, "AUDIO"),
+            Plane(n) => write!(f, "PLANE {}", n),
+            Moved(x) => write!(f, "LD V{:X}, DT", x),
+            Key(x) => write!(f, "LD V{:X}, K", x),
+            Setrd(x) => write!(f, "LD DT, V{:X}", x),
+            Setrs(x) => write!(f, "LD ST, V{:X}", x),
+            Addi(x) => write!(f, "ADD I, V{:X}", x),
+            Ldfnt(x) => write!(f, "LD F, V{:X}", x),
+            Ldbigfnt(x) => write!(f, "LD HF, V{:X}", x),
+            SetPitch(x) => write!(f, "PITCH V{:X}", x),
+            Bcd(x) => write!(f, "LD B, V{:X}", x),
+            Store(x) => write!(f, "LD [I], V{:X}", x),
+            Load(x) => write!(f, "LD V{:X}, [I]", x),
+            StoreFlags(x) => write!(f, "LD R, V{:X}", x),
+            LoadFlags(x) => write!(f, "LD V{:X}, R", x),
         }
     }
 }
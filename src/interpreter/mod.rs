@@ -1,44 +1,197 @@
+mod diff;
+mod downscale;
+mod error;
 mod font;
+mod info;
+mod init;
+mod input;
 mod instruction;
+mod platform;
+mod quirks;
+mod rng;
+mod save;
+#[cfg(test)]
+mod tests;
+mod timing;
+mod trace;
 
+use alloc::boxed::Box;
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
 use chip8_base::{Display, Interpreter, Keys, Pixel};
-use instruction::Instruction;
-use rand::random;
+use core::fmt::Write;
+use core::time::Duration;
+pub use diff::DisplayDiff;
+pub use downscale::DownscaleMode;
+pub use error::{Chip8Error, CodeWritePolicy, DecodeError, DecodePolicy, SysPolicy};
+pub use info::{crc32, RomInfo};
+pub use init::{MemoryInit, ResetPolicy};
+pub use input::InputState;
+pub use instruction::Instruction;
+pub use platform::{detect_platform, Platform};
+pub use quirks::{BitOrder, Quirks};
+use rng::SplitMix64;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+#[cfg(feature = "std")]
 use std::path::PathBuf;
-use std::time::Duration;
-use std::{fs, io};
+pub use timing::{cycle_cost, TimerMode, TimingModel};
+pub use trace::{StepOutcome, TraceRecord, WatchKind};
 
-// TODO: add log macros (task 2.3/3.8)
+/// Default time between decrements of the delay and sound timers (60Hz)
+const TIMER_PERIOD: Duration = Duration::from_nanos(16666667);
 
+/// Number of recently fetched opcodes kept for `dump_diagnostics`
+const HISTORY_LEN: usize = 16;
+
+/// A single bit plane of the display, sized for hires mode
+type Plane = [[Pixel; 128]; 64];
+
+/// Called with the address and instruction before each instruction is executed
+pub type PreExecHook = Box<dyn FnMut(u16, &Instruction) + Send>;
+/// Called with the address and instruction, and the resulting state, after each instruction
+pub type PostExecHook = Box<dyn FnMut(u16, &Instruction, &ChipState) + Send>;
+
+/// The instrumentation callbacks and trace output, which a clone of the state starts without
+#[derive(Default)]
+struct ExecHooks {
+    pre: Option<PreExecHook>,
+    post: Option<PostExecHook>,
+    #[cfg(feature = "trace_json")]
+    trace_json: Option<Box<dyn io::Write + Send>>,
+}
+
+impl Clone for ExecHooks {
+    fn clone(&self) -> Self {
+        ExecHooks::default()
+    }
+}
+
+/// The complete state of the interpreter.
+///
+/// Cloning gives an independent copy, e.g. to load a ROM once and run it on several
+/// threads. The copy's random number generator continues from the same state, so use
+/// `reseed` if the clones shouldn't see the same Cxkk results. Exec hooks are not cloned.
+#[derive(Clone)]
 pub struct ChipState {
-    memory: [u8; 4096],
+    memory: [u8; 65536],
     registers: [u8; 16],
     pc: u16,
+    /// Address the ROM was loaded at, and where execution begins
+    start: u16,
+    /// Length of the loaded ROM in bytes
+    rom_len: usize,
+    /// CRC-32 of the ROM as it was loaded, before it could modify itself
+    rom_crc32: u32,
+    /// Address of the 4x5 digit font used by Fx29
+    font_offset: u16,
     index: u16,
     pointer: u8,
-    stack: [u16; 16],
-    display: [[Pixel; 64]; 32],
+    stack: Vec<u16>,
+    /// SUPER-CHIP's persistent HP-48 flag registers, used by Fx75/Fx85
+    flags: [u8; 8],
+    display: [Plane; 2],
+    /// Pixels lit by the host with `set_pixel`, drawn over the display but never collided with
+    overlay: Plane,
+    hires: bool,
+    planes: u8,
+    dirty: Option<DisplayDiff>,
+    /// The 64x32 frame built from the display, handed out by reference from `display`
+    frame: Display,
+    /// Whether the last step rebuilt `frame`
+    frame_changed: bool,
+    /// Set when `step_compute` changes the display without rebuilding `frame`
+    frame_stale: bool,
+    /// Set when the display changed since `step_limited` last returned a frame
+    frame_pending: bool,
+    /// Whether the timers ticked during the last step
+    ticked: bool,
+    downscale: DownscaleMode,
+    /// Brightness of each frame pixel, updated every timer tick
+    intensity: [[u8; 64]; 32],
+    phosphor_decay: u8,
     speed: Duration,
+    timing_model: TimingModel,
+    timer_mode: TimerMode,
+    /// Steps since the last timer tick under `TimerMode::CycleLocked`
+    locked_steps: u32,
     ticker: Duration,
+    /// Time between decrements of the delay and sound timers
+    timer_period: Duration,
+    /// Set while `step_frame` runs, which ticks the timers once per frame instead
+    frame_batching: bool,
     delay_timer: u8,
     sound_timer: u8,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    /// Whether the buzzer was sounding at the end of the previous step
+    buzzer_was_active: bool,
+    buzzer_edge: Option<bool>,
+    quirks: Quirks,
+    rng: SplitMix64,
+    waiting_for_vblank: bool,
+    /// Set when the last running step re-queued a Dxyn to wait for the vertical blank
+    draw_deferred: bool,
+    /// Register a pending Fx0A will store the key in
+    waiting_for_key: Option<u8>,
+    key_held: Option<u8>,
+    keymap: [usize; 16],
+    /// The keys passed to the last `key_history_len` running steps, oldest first
+    key_history: VecDeque<Keys>,
+    key_history_len: usize,
+    halted: bool,
+    /// Halt on a jump to the jump's own address
+    detect_halt: bool,
+    /// Halt on a 0000 opcode, as when running off the end of a ROM into zeroed memory
+    halt_on_zero: bool,
+    paused: bool,
+    cycles: u64,
+    /// The last HISTORY_LEN (pc, opcode) pairs fetched, oldest first
+    history: VecDeque<(u16, u16)>,
+    trace_registers: bool,
+    /// Drop Fx33/Fx55 writes that target the interpreter area below 0x200
+    protect_reserved: bool,
+    breakpoints: BTreeSet<u16>,
+    breakpoint_hit: Option<u16>,
+    /// Ordered so the same watchpoint is reported first when several change at once
+    watchpoints: BTreeSet<WatchKind>,
+    decode_policy: DecodePolicy,
+    sys_policy: SysPolicy,
+    /// Address of the last 0nnn trapped under `SysPolicy::Trap` or `SysPolicy::Halt`
+    last_sys: Option<u16>,
+    code_write_policy: CodeWritePolicy,
+    /// End of the code executed so far, which runs from `start` up to the highest pc fetched
+    code_end: usize,
+    /// Address of the last write into code reported under `CodeWritePolicy::Warn`
+    last_code_write: Option<u16>,
+    hooks: ExecHooks,
 }
 
-impl Interpreter for ChipState {
-    fn step(&mut self, keys: &Keys) -> Option<Display> {
-        let opcode = self.fetch();
-        let instruction = Instruction::decode(opcode);
+/// The `i`th register of the range Vx to Vy, which counts down if x > y (XO-CHIP 5xy2/5xy3).
+fn range_register(x: u8, y: u8, i: usize) -> usize {
+    if x <= y {
+        x as usize + i
+    } else {
+        x as usize - i
+    }
+}
 
-        // Handle timers
-        self.ticker = self.ticker.saturating_sub(self.speed());
-        if self.ticker == Duration::ZERO {
-            self.delay_timer = self.delay_timer.saturating_sub(1);
-            self.sound_timer = self.sound_timer.saturating_sub(1);
-            self.ticker = Duration::from_nanos(16666667);
-        }
+/// Time per instruction at `clock_freq`, falling back to 700Hz for an invalid 0Hz clock.
+fn clock_speed(clock_freq: u32) -> Duration {
+    let clock_freq = if clock_freq == 0 {
+        log::warn!("Clock frequency of 0Hz is invalid, using 700Hz");
+        700
+    } else {
+        clock_freq
+    };
+    Duration::from_secs_f64(1_f64 / clock_freq as f64)
+}
 
-        log::debug!("Executing instruction {:?}", instruction);
-        self.execute(instruction, keys)
+impl Interpreter for ChipState {
+    fn step(&mut self, keys: &Keys) -> Option<Display> {
+        self.step_with_trace(keys).0
     }
 
     fn speed(&self) -> Duration {
@@ -52,83 +205,1196 @@ impl Interpreter for ChipState {
 
 impl ChipState {
     pub fn new(clock_freq: u32) -> Self {
-        let mut memory = [0; 4096];
-        // Load font into memory (font is 80 bytes)
+        Self::with_quirks(clock_freq, Quirks::default())
+    }
+
+    pub fn with_quirks(clock_freq: u32, quirks: Quirks) -> Self {
+        let mut memory = [0; 65536];
+        // Load font into memory (font is 80 bytes, big font is 100 bytes)
         memory[0x50..0xA0].copy_from_slice(&font::FONT);
+        memory[0xA0..0x104].copy_from_slice(&font::BIG_FONT);
 
         ChipState {
             memory,
             registers: [0; 16],
             pc: 0x200,
+            start: 0x200,
+            rom_len: 0,
+            rom_crc32: 0,
+            font_offset: 0x50,
             index: 0,
             pointer: 0,
-            stack: [0; 16],
-            display: [[Pixel::default(); 64]; 32],
-            speed: Duration::from_secs_f64(1_f64 / clock_freq as f64),
-            ticker: Duration::from_nanos(16666667),
+            stack: vec![0; 16],
+            flags: [0; 8],
+            display: [[[Pixel::default(); 128]; 64]; 2],
+            overlay: [[Pixel::default(); 128]; 64],
+            hires: false,
+            planes: 0b01,
+            dirty: None,
+            frame: [[Pixel::default(); 64]; 32],
+            frame_changed: false,
+            frame_stale: false,
+            frame_pending: false,
+            ticked: false,
+            downscale: DownscaleMode::default(),
+            intensity: [[0; 64]; 32],
+            phosphor_decay: u8::MAX,
+            speed: clock_speed(clock_freq),
+            timing_model: TimingModel::default(),
+            timer_mode: TimerMode::default(),
+            locked_steps: 0,
+            ticker: Duration::ZERO,
+            timer_period: TIMER_PERIOD,
+            frame_batching: false,
             delay_timer: 0,
             sound_timer: 0,
+            audio_pattern: None,
+            pitch: 64,
+            buzzer_was_active: false,
+            buzzer_edge: None,
+            quirks,
+            rng: SplitMix64::from_entropy(),
+            waiting_for_vblank: false,
+            draw_deferred: false,
+            waiting_for_key: None,
+            key_held: None,
+            keymap: core::array::from_fn(|key| key),
+            key_history: VecDeque::new(),
+            key_history_len: 0,
+            halted: false,
+            detect_halt: false,
+            halt_on_zero: false,
+            paused: false,
+            cycles: 0,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            trace_registers: false,
+            protect_reserved: false,
+            breakpoints: BTreeSet::new(),
+            breakpoint_hit: None,
+            watchpoints: BTreeSet::new(),
+            decode_policy: DecodePolicy::default(),
+            sys_policy: SysPolicy::default(),
+            last_sys: None,
+            code_write_policy: CodeWritePolicy::default(),
+            code_end: 0x200,
+            last_code_write: None,
+            hooks: ExecHooks::default(),
+        }
+    }
+
+    /// Replaces the 4x5 digit font used by Fx29 with `font`, loaded at `offset` instead of the
+    /// built-in font at 0x50. The font must fit below 0x200; placing it over 0xA0-0x103
+    /// replaces part of the 8x10 font used by Fx30.
+    pub fn set_font(&mut self, font: &[u8; 80], offset: u16) -> Result<(), Chip8Error> {
+        let start = offset as usize;
+        if start + font.len() > 0x200 {
+            return Err(Chip8Error::FontOutOfRange { offset });
+        }
+
+        let old = self.font_offset as usize;
+        self.memory[old..old + font.len()].fill(0);
+        self.memory[start..start + font.len()].copy_from_slice(font);
+        self.font_offset = offset;
+        Ok(())
+    }
+
+    /// Runs a single step like `Interpreter::step`, also reporting what was executed.
+    pub fn step_with_trace(&mut self, keys: &Keys) -> (Option<Display>, TraceRecord) {
+        match self.step_inner(keys, true) {
+            (Ok(StepOutcome::Executed(_) | StepOutcome::Watchpoint { .. }), trace) => {
+                (self.changed_frame(), trace)
+            }
+            (_, trace) => (None, trace),
+        }
+    }
+
+    /// Runs a single step, reporting when a breakpoint stopped it from executing or it
+    /// changed a watched value.
+    pub fn step_debug(&mut self, keys: &Keys) -> StepOutcome {
+        match self.step_inner(keys, true).0 {
+            Ok(StepOutcome::Executed(_)) | Err(_) => StepOutcome::Executed(self.changed_frame()),
+            Ok(outcome) => outcome,
+        }
+    }
+
+    /// Runs a single step like `Interpreter::step`, returning the error that halted the
+    /// interpreter if the instruction failed.
+    pub fn try_step(&mut self, keys: &Keys) -> Result<Option<Display>, Chip8Error> {
+        match self.step_inner(keys, true).0? {
+            StepOutcome::Executed(_) | StepOutcome::Watchpoint { .. } => Ok(self.changed_frame()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Runs a single step like `Interpreter::step`, returning whether the display changed
+    /// instead of a copy of it. Hosts that can draw from a reference read it with `display`.
+    pub fn step_in_place(&mut self, keys: &Keys) -> bool {
+        // Errors have already been logged and halted the interpreter, as in `step`
+        let _ = self.step_inner(keys, true);
+        self.frame_changed
+    }
+
+    /// Runs a single step like `Interpreter::step`, but returns at most one frame per timer
+    /// period of emulated time. Draws in between are coalesced into the frame returned on
+    /// the step that ticks the timers, so draw-heavy ROMs render at a steady 60fps. A
+    /// pending frame is also returned once the interpreter halts or is paused, as the timers
+    /// won't tick again until it runs.
+    pub fn step_limited(&mut self, keys: &Keys) -> Option<Display> {
+        // Errors have already been logged and halted the interpreter, as in `step`
+        let _ = self.step_inner(keys, true);
+        self.frame_pending |= self.frame_changed;
+        if (self.ticked || self.halted || self.paused) && self.frame_pending {
+            self.frame_pending = false;
+            Some(self.frame)
+        } else {
+            None
+        }
+    }
+
+    /// The 64x32 frame as of the last step, the same one `Interpreter::step` returns a copy
+    /// of. Not updated by `step_compute` until the next step that builds frames.
+    pub fn display(&self) -> &Display {
+        &self.frame
+    }
+
+    /// A copy of the frame if the last step changed the display.
+    fn changed_frame(&self) -> Option<Display> {
+        self.frame_changed.then_some(self.frame)
+    }
+
+    /// Runs a single step like `Interpreter::step` without building a frame when the
+    /// display changes, for benchmarks and headless runs that never look at it.
+    pub fn step_compute(&mut self, keys: &Keys) {
+        // Errors have already been logged and halted the interpreter, as in `step`
+        let _ = self.step_inner(keys, false);
+    }
+
+    fn step_inner(
+        &mut self,
+        keys: &Keys,
+        render: bool,
+    ) -> (Result<StepOutcome, Chip8Error>, TraceRecord) {
+        // Steps that can't run see no input, so aren't worth replaying
+        if self.key_history_len > 0 && !self.paused && !self.halted {
+            if self.key_history.len() == self.key_history_len {
+                self.key_history.pop_front();
+            }
+            self.key_history.push_back(*keys);
+        }
+
+        let watched: Vec<(WatchKind, u8)> = self
+            .watchpoints
+            .iter()
+            .map(|&kind| (kind, self.watched_value(kind)))
+            .collect();
+
+        let mut result = self.step_cycle(keys, render);
+
+        // Report the first watched value the step changed, in place of the display
+        if let Ok(StepOutcome::Executed(_)) = result.0 {
+            for (kind, old) in watched {
+                let new = self.watched_value(kind);
+                if new != old {
+                    result.0 = Ok(StepOutcome::Watchpoint { kind, old, new });
+                    break;
+                }
+            }
+        }
+
+        let buzzer_active = self.sound_timer != 0;
+        self.buzzer_edge = (buzzer_active != self.buzzer_was_active).then_some(buzzer_active);
+        self.buzzer_was_active = buzzer_active;
+
+        result
+    }
+
+    /// Fetches and executes one instruction, rebuilding the frame if the display changed
+    /// and `render` is set. Whether it did is left in `frame_changed`.
+    fn step_cycle(
+        &mut self,
+        keys: &Keys,
+        render: bool,
+    ) -> (Result<StepOutcome, Chip8Error>, TraceRecord) {
+        let mut trace = TraceRecord {
+            pc: self.pc,
+            opcode: 0,
+            instruction: None,
+        };
+        self.frame_changed = false;
+        self.ticked = false;
+
+        if self.halted || self.paused {
+            return (Ok(StepOutcome::Executed(None)), trace);
+        }
+        let retrying_draw = core::mem::take(&mut self.draw_deferred);
+
+        // Fx0A blocks fetching until a key is pressed and released, but timers keep running
+        if self.waiting_for_key.is_some() {
+            self.cycles += 1;
+            self.tick_timers(self.instruction_time(&Instruction::Key(0)));
+            self.poll_key(keys);
+            return (Ok(StepOutcome::Executed(None)), trace);
+        }
+
+        // Stop once at a breakpoint; the next step executes the instruction there. A deferred
+        // Dxyn retried at the same pc has already stopped there.
+        if !retrying_draw
+            && self.breakpoints.contains(&self.pc)
+            && self.breakpoint_hit != Some(self.pc)
+        {
+            self.breakpoint_hit = Some(self.pc);
+            return (Ok(StepOutcome::Breakpoint(self.pc)), trace);
+        }
+        self.breakpoint_hit = None;
+        self.cycles += 1;
+
+        trace.opcode = self.fetch();
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back((trace.pc, trace.opcode));
+        self.code_end = self.code_end.max(trace.pc as usize + 2);
+
+        if self.halt_on_zero && trace.opcode == 0x0000 {
+            log::info!("Opcode 0000 at {:#05X}, halting interpreter", trace.pc);
+            self.pc = trace.pc;
+            self.halted = true;
+            return (Ok(StepOutcome::Executed(None)), trace);
+        }
+
+        let decoded =
+            Instruction::decode(trace.opcode, trace.pc).map(|instruction| match instruction {
+                // F000 NNNN takes its address from the following word (XO-CHIP)
+                Instruction::LoadILong(_) => Instruction::LoadILong(self.fetch()),
+                instruction => instruction,
+            });
+
+        // Every 0nnn that isn't another instruction decodes to Nop
+        if matches!(decoded, Ok(Instruction::Nop)) && self.sys_policy != SysPolicy::Ignore {
+            let addr = trace.opcode & 0x0FFF;
+            log::warn!("SYS {:#05X} at {:#05X} can't be run", addr, trace.pc);
+            self.last_sys = Some(addr);
+            if self.sys_policy == SysPolicy::Halt {
+                log::info!("Halting interpreter on SYS call");
+                self.pc = trace.pc;
+                self.halted = true;
+                return (Ok(StepOutcome::Executed(None)), trace);
+            }
+        }
+
+        match decoded {
+            Ok(instruction) => self.tick_timers(self.instruction_time(&instruction)),
+            Err(_) => self.tick_timers(self.speed),
+        }
+
+        let result = decoded
+            .or_else(|e| match self.decode_policy {
+                DecodePolicy::Halt => Err(Chip8Error::from(e)),
+                DecodePolicy::Nop => {
+                    log::warn!("{}, treating as Nop", e);
+                    Ok(Instruction::Nop)
+                }
+            })
+            .and_then(|instruction| {
+                trace.instruction = Some(instruction);
+                log::debug!("Executing instruction {:?}", instruction);
+                if let Some(hook) = &mut self.hooks.pre {
+                    hook(trace.pc, &instruction);
+                }
+                let result = self.execute(instruction, keys);
+                // Taken out while it runs so the hook can borrow the whole state
+                if let Some(mut hook) = self.hooks.post.take() {
+                    hook(trace.pc, &instruction, self);
+                    self.hooks.post = Some(hook);
+                }
+                result
+            });
+
+        if let Err(e) = &result {
+            log::error!("{}, halting interpreter", e);
+            log::error!("{}", self.dump_diagnostics());
+            self.halted = true;
+        }
+
+        if self.trace_registers && log::log_enabled!(log::Level::Trace) {
+            if let Some(instruction) = trace.instruction {
+                log::trace!(
+                    "pc={:#05X} opcode={:#06X} {} I={:#05X} V={:02X?}",
+                    trace.pc,
+                    trace.opcode,
+                    instruction,
+                    self.index,
+                    self.registers
+                );
+            }
+        }
+
+        #[cfg(feature = "trace_json")]
+        self.write_trace_json(&trace);
+
+        if let Ok(drawn) = result {
+            self.frame_stale |= drawn;
+        }
+        if render && self.frame_stale {
+            self.refresh_frame();
+            self.frame_changed = true;
+        }
+
+        (result.map(|_| StepOutcome::Executed(None)), trace)
+    }
+
+    /// Writes one line of JSON for each executed instruction to `writer`, for external
+    /// debuggers. Tracing stops if a write fails.
+    #[cfg(feature = "trace_json")]
+    pub fn set_trace_json(&mut self, writer: Box<dyn io::Write + Send>) {
+        self.hooks.trace_json = Some(writer);
+    }
+
+    #[cfg(feature = "trace_json")]
+    fn write_trace_json(&mut self, trace: &TraceRecord) {
+        if trace.instruction.is_none() {
+            return;
+        }
+        if let Some(writer) = &mut self.hooks.trace_json {
+            let line = trace.to_json(&self.registers, self.index);
+            if let Err(e) = writeln!(writer, "{}", line) {
+                log::warn!("Failed to write trace, stopping tracing: {}", e);
+                self.hooks.trace_json = None;
+            }
+        }
+    }
+
+    /// Runs `ipf` instructions followed by a single timer tick, as one 60Hz frame, returning
+    /// the display if any of them changed it. The timers ignore the clock frequency while
+    /// frames are stepped this way.
+    ///
+    /// Under the `display_wait` quirk at most one Dxyn draws per frame. The frame ends early
+    /// at the next one, which is left at the pc to draw after the tick. A breakpoint or
+    /// watchpoint also ends the frame early, as it would stop `step_debug`.
+    pub fn step_frame(&mut self, ipf: usize, keys: &Keys) -> Option<Display> {
+        let buzzer_was_active = self.buzzer_was_active;
+        self.frame_batching = true;
+        let mut display = None;
+        for _ in 0..ipf {
+            let outcome = self.step_debug(keys);
+            display = self.changed_frame().or(display);
+            // The deferred draw would only be retried until the frame ends
+            if !matches!(outcome, StepOutcome::Executed(_)) || self.draw_deferred {
+                break;
+            }
+        }
+        self.frame_batching = false;
+
+        if !self.halted && !self.paused {
+            self.apply_ticks(1);
+        }
+
+        // Report the buzzer edge across the whole frame, tick included
+        let buzzer_active = self.sound_timer != 0;
+        self.buzzer_edge = (buzzer_active != buzzer_was_active).then_some(buzzer_active);
+        self.buzzer_was_active = buzzer_active;
+        display
+    }
+
+    /// Steps up to `max_steps` times without a window, collecting every frame produced.
+    pub fn run_headless(
+        &mut self,
+        max_steps: usize,
+        mut keys_provider: impl FnMut() -> Keys,
+    ) -> Vec<Display> {
+        (0..max_steps)
+            .filter_map(|_| self.step(&keys_provider()))
+            .collect()
+    }
+
+    /// Steps once with an owned key state, for scripted input.
+    pub fn step_with_keys(&mut self, keys: Keys) -> Option<Display> {
+        self.step(&keys)
+    }
+
+    /// Holds each key state for its number of steps in turn, collecting every frame produced.
+    pub fn replay(&mut self, frames: &[(Keys, usize)]) -> Vec<Display> {
+        let mut displays = Vec::new();
+        for &(keys, steps) in frames {
+            for _ in 0..steps {
+                displays.extend(self.step_with_keys(keys));
+            }
         }
+        displays
+    }
+
+    /// Steps `n` times with the same keys, returning the last frame produced, if any.
+    pub fn run_cycles(&mut self, n: u64, keys: &Keys) -> Option<Display> {
+        (0..n).filter_map(|_| self.step(keys)).last()
     }
 
-    pub fn load(&mut self, rom: PathBuf) -> io::Result<()> {
-        fs::read(rom).and_then(|bytes| {
-            self.memory[0x200..0x200 + bytes.len()].copy_from_slice(&bytes);
-            self.pc = 0x200;
-            Ok(())
+    /// Number of steps taken while running since the interpreter was created, including
+    /// steps spent waiting on Fx0A or retrying a deferred draw. Steps taken while halted or
+    /// paused, or stopped at a breakpoint, aren't counted.
+    pub fn cycle_count(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Renders the display at its active resolution (64x32, or 128x64 in hires mode) as a
+    /// row-major RGBA buffer, using `on` for pixels lit on any plane or the overlay and `off`
+    /// for the rest.
+    pub fn render_rgb(&self, on: [u8; 3], off: [u8; 3]) -> Vec<u8> {
+        let (width, height) = (self.width(), self.height());
+        let mut buffer = Vec::with_capacity(width * height * 4);
+
+        for y in 0..height {
+            for x in 0..width {
+                let lit = self.lit(x, y);
+                buffer.extend_from_slice(if lit { &on } else { &off });
+                buffer.push(0xFF);
+            }
+        }
+
+        buffer
+    }
+
+    /// Renders the display at its active resolution as text, with `#` for pixels lit on any
+    /// plane or the overlay and a space for the rest. See `render::to_ascii`.
+    pub fn render_ascii(&self) -> String {
+        let rows: Vec<Vec<Pixel>> = (0..self.height())
+            .map(|y| {
+                (0..self.width())
+                    .map(|x| Pixel::try_from(self.lit(x, y) as u8).unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        crate::render::to_ascii(&rows)
+    }
+
+    /// The pixel at (`x`, `y`), lit if it is on any plane or the overlay, or `None` outside the
+    /// active resolution.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Pixel> {
+        if x < self.width() && y < self.height() {
+            Some(Pixel::try_from(self.lit(x, y) as u8).unwrap_or_default())
+        } else {
+            None
+        }
+    }
+
+    /// Sets the pixel at (`x`, `y`) on an overlay drawn over the display, e.g. for a cursor.
+    /// The overlay never collides with sprites, and an unlit pixel shows the display beneath.
+    /// Returns false, leaving the overlay alone, outside the active resolution.
+    pub fn set_pixel(&mut self, x: usize, y: usize, pixel: Pixel) -> bool {
+        if x >= self.width() || y >= self.height() {
+            return false;
+        }
+        self.overlay[y][x] = pixel;
+        self.mark_dirty(DisplayDiff::pixel(x, y));
+        self.refresh_frame();
+        true
+    }
+
+    /// Clears every pixel set with `set_pixel`.
+    pub fn clear_overlay(&mut self) {
+        self.overlay = [[Pixel::default(); 128]; 64];
+        self.mark_all_dirty();
+        self.refresh_frame();
+    }
+
+    /// Whether the pixel at (`x`, `y`) is lit on any plane or the overlay.
+    fn lit(&self, x: usize, y: usize) -> bool {
+        self.display.iter().any(|plane| plane[y][x].into()) || self.overlay[y][x].into()
+    }
+
+    /// The length, checksum and extent of the loaded ROM, or None if none has been loaded.
+    pub fn rom_info(&self) -> Option<RomInfo> {
+        (self.rom_len > 0).then(|| RomInfo {
+            len: self.rom_len,
+            crc32: self.rom_crc32,
+            start: self.start,
+            end: self.start + (self.rom_len - 1) as u16,
         })
     }
 
-    fn fetch(&mut self) -> u16 {
-        let instruction = u16::from_be_bytes([
-            self.memory[self.pc as usize],
-            self.memory[(self.pc + 1) as usize],
-        ]);
+    /// Decodes the loaded ROM as it currently is in memory, yielding the address and
+    /// instruction of each word that decodes. See `disasm::instructions`.
+    pub fn instructions(&self) -> impl Iterator<Item = (u16, Instruction)> + '_ {
+        let start = self.start as usize;
+        crate::disasm::instructions(&self.memory[start..start + self.rom_len], self.start)
+    }
+
+    /// Brightness of each pixel of the 64x32 frame, for hosts emulating phosphor persistence.
+    /// Lit pixels are at full brightness, and unlit ones fade out at the rate set by
+    /// `set_phosphor_decay`. Only affects rendering; collisions still use the display.
+    pub fn render_intensity(&self) -> [[u8; 64]; 32] {
+        let mut intensity = self.intensity;
+        for (intensity_row, frame_row) in intensity.iter_mut().zip(self.current_frame().iter()) {
+            for (intensity, &pixel) in intensity_row.iter_mut().zip(frame_row.iter()) {
+                if pixel.into() {
+                    *intensity = u8::MAX;
+                }
+            }
+        }
+        intensity
+    }
+
+    /// Sets how hires 2x2 blocks collapse into the 64x32 frame. Defaults to
+    /// `DownscaleMode::OrSample`.
+    pub fn set_downscale_mode(&mut self, mode: DownscaleMode) {
+        self.downscale = mode;
+        self.refresh_frame();
+    }
+
+    /// Sets how much an unlit pixel's intensity fades each timer tick. The default of 255
+    /// turns pixels off instantly.
+    pub fn set_phosphor_decay(&mut self, per_tick: u8) {
+        self.phosphor_decay = per_tick;
+    }
+
+    /// Writes the 64x32 frame to `path` as a PBM image. See `render::to_pbm`.
+    #[cfg(feature = "std")]
+    pub fn save_screenshot(&self, path: PathBuf) -> io::Result<()> {
+        std::fs::write(path, crate::render::to_pbm(&self.build_frame()))
+    }
+
+    /// A stable 64-bit FNV-1a hash of the display at its active resolution, for comparing
+    /// displays in tests. Each plane is hashed in turn, row by row, as a 0 or 1 byte per pixel.
+    pub fn display_hash(&self) -> u64 {
+        const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+        let (width, height) = (self.width(), self.height());
+        self.display
+            .iter()
+            .flat_map(|plane| plane[..height].iter().flat_map(|row| &row[..width]))
+            .fold(FNV_OFFSET, |hash, &pixel| {
+                (hash ^ bool::from(pixel) as u64).wrapping_mul(FNV_PRIME)
+            })
+    }
+
+    /// Returns the area of the display that changed since this was last called, if any.
+    pub fn take_dirty_region(&mut self) -> Option<DisplayDiff> {
+        self.dirty.take()
+    }
+
+    /// Formats `len` bytes of memory from `start` as a hexdump of 16 bytes per line, with
+    /// printable ASCII alongside. The range is clamped to the addressable memory.
+    pub fn hexdump(&self, start: u16, len: usize) -> String {
+        let start = (start as usize).min(self.memory_size());
+        let end = start.saturating_add(len).min(self.memory_size());
+        let mut out = String::new();
+
+        for (i, line) in self.memory[start..end].chunks(16).enumerate() {
+            let _ = write!(out, "{:#05X}: ", start + i * 16);
+            for byte in line {
+                let _ = write!(out, "{:02X} ", byte);
+            }
+            // Pad a short last line so the ASCII column lines up
+            out.push_str(&"   ".repeat(16 - line.len()));
+            let ascii: String = line
+                .iter()
+                .map(|&byte| match byte {
+                    0x20..=0x7E => byte as char,
+                    _ => '.',
+                })
+                .collect();
+            let _ = writeln!(out, "|{}|", ascii);
+        }
+
+        out
+    }
+
+    /// Formats the registers, stack and recently fetched opcodes for post-mortem debugging.
+    pub fn dump_diagnostics(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "pc: {:#05X}  I: {:#05X}  DT: {}  ST: {}",
+            self.pc, self.index, self.delay_timer, self.sound_timer
+        );
+        for (i, chunk) in self.registers.chunks(8).enumerate() {
+            let _ = writeln!(out, "V{:X}-V{:X}: {:02X?}", i * 8, i * 8 + 7, chunk);
+        }
+        let _ = writeln!(out, "stack: {:03X?}", self.stack());
+        let _ = writeln!(out, "recent opcodes (oldest first):");
+        for (pc, opcode) in &self.history {
+            let _ = writeln!(out, "  {:#05X}: {:04X}", pc, opcode);
+        }
+        out
+    }
+
+    /// Logs the pc, opcode, mnemonic and registers after every instruction at trace level.
+    pub fn set_trace_registers(&mut self, enabled: bool) {
+        self.trace_registers = enabled;
+    }
+
+    /// When enabled, Fx33, Fx55 and 5xy2 writes that would land below 0x200, including by
+    /// wrapping past the end of memory, log a warning instead of writing, so a buggy ROM
+    /// cannot overwrite the fonts.
+    pub fn set_protect_reserved(&mut self, enabled: bool) {
+        self.protect_reserved = enabled;
+    }
+
+    /// Sets whether the timers follow emulated time or tick on a fixed step count.
+    pub fn set_timer_mode(&mut self, mode: TimerMode) {
+        self.timer_mode = mode;
+    }
+
+    /// Sets how much emulated time each instruction takes, which drives the timers.
+    pub fn set_timing_model(&mut self, model: TimingModel) {
+        self.timing_model = model;
+    }
+
+    /// Sets a callback run before every instruction, e.g. for tracing or profiling.
+    pub fn set_pre_exec_hook(&mut self, hook: PreExecHook) {
+        self.hooks.pre = Some(hook);
+    }
+
+    /// Sets a callback run after every instruction, which can inspect the new state.
+    pub fn set_post_exec_hook(&mut self, hook: PostExecHook) {
+        self.hooks.post = Some(hook);
+    }
+
+    pub fn clear_exec_hooks(&mut self) {
+        self.hooks = ExecHooks::default();
+    }
+
+    pub fn set_decode_policy(&mut self, policy: DecodePolicy) {
+        self.decode_policy = policy;
+    }
+
+    /// Sets what happens on a 0nnn SYS call. By default they're ignored as a Nop.
+    pub fn set_sys_policy(&mut self, policy: SysPolicy) {
+        self.sys_policy = policy;
+    }
+
+    /// The address of the last SYS call trapped under `SysPolicy::Trap` or `SysPolicy::Halt`.
+    pub fn last_sys(&self) -> Option<u16> {
+        self.last_sys
+    }
+
+    /// Sets what happens when Fx33, Fx55 or 5xy2 writes over code that has already run.
+    /// By default such writes are allowed without checking.
+    pub fn set_code_write_policy(&mut self, policy: CodeWritePolicy) {
+        self.code_write_policy = policy;
+    }
+
+    /// The address of the last write into code reported under `CodeWritePolicy::Warn`.
+    pub fn last_code_write(&self) -> Option<u16> {
+        self.last_code_write
+    }
+
+    /// Sets which slot of `Keys` each CHIP-8 key 0-F is read from.
+    pub fn set_keymap(&mut self, keymap: [usize; 16]) {
+        self.keymap = keymap;
+    }
+
+    /// Records the keys passed to the last `len` steps for `key_history`, skipping steps
+    /// taken while paused or halted. 0, the default, turns recording off.
+    pub fn set_key_history(&mut self, len: usize) {
+        self.key_history_len = len;
+        let excess = self.key_history.len().saturating_sub(len);
+        self.key_history.drain(..excess);
+    }
+
+    /// The recorded key states, oldest first.
+    pub fn key_history(&mut self) -> &[Keys] {
+        self.key_history.make_contiguous()
+    }
+
+    /// Stops execution before the instruction at `addr` each time it is reached.
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    /// Stops after any instruction that changes register `r`.
+    pub fn watch_register(&mut self, r: u8) {
+        self.watchpoints.insert(WatchKind::Register(r & 0xF));
+    }
+
+    /// Stops after any instruction that changes the byte at `addr`.
+    pub fn watch_memory(&mut self, addr: u16) {
+        self.watchpoints.insert(WatchKind::Memory(addr));
+    }
+
+    pub fn remove_watchpoint(&mut self, kind: WatchKind) {
+        self.watchpoints.remove(&kind);
+    }
+
+    fn watched_value(&self, kind: WatchKind) -> u8 {
+        match kind {
+            WatchKind::Register(r) => self.registers[r as usize],
+            WatchKind::Memory(addr) => self.memory[addr as usize],
+        }
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    /// The SUPER-CHIP flag registers. These survive `reset`, and a host can save them
+    /// with `flags` and restore them with `set_flags` to persist them between runs.
+    pub fn flags(&self) -> &[u8; 8] {
+        &self.flags
+    }
+
+    pub fn set_flags(&mut self, flags: [u8; 8]) {
+        self.flags = flags;
+    }
+
+    // The setters below are for building precise scenarios in tests and fuzzers. Each
+    // returns false, leaving the state alone, if its target is out of range.
+
+    /// Sets register V`r`.
+    pub fn set_register(&mut self, r: u8, value: u8) -> bool {
+        match self.registers.get_mut(r as usize) {
+            Some(register) => {
+                *register = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets I, which must be within the addressable memory.
+    pub fn set_index(&mut self, index: u16) -> bool {
+        if index as usize >= self.memory_size() {
+            return false;
+        }
+        self.index = index;
+        true
+    }
+
+    /// Sets the program counter, which must be within the addressable memory.
+    pub fn set_pc(&mut self, pc: u16) -> bool {
+        if pc as usize >= self.memory_size() {
+            return false;
+        }
+        self.pc = pc;
+        true
+    }
+
+    /// Copies `bytes` into memory at `addr`, which must all fit in the addressable memory.
+    pub fn set_memory(&mut self, addr: u16, bytes: &[u8]) -> bool {
+        let addr = addr as usize;
+        if addr + bytes.len() > self.memory_size() {
+            return false;
+        }
+        self.memory[addr..addr + bytes.len()].copy_from_slice(bytes);
+        true
+    }
 
+    /// The addressable memory: 4K, or 64K with the `extended_memory` quirk.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..self.memory_size()]
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// The return addresses currently on the stack, oldest first.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.pointer as usize]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// The 128-bit XO-CHIP sample loaded by F002, played while the sound timer is active.
+    /// `None` means no pattern has been loaded and the plain buzzer should be used.
+    pub fn audio_pattern(&self) -> Option<&[u8; 16]> {
+        self.audio_pattern.as_ref()
+    }
+
+    /// `Some(true)` if the buzzer started sounding during the last step, or the last frame
+    /// under `step_frame`, `Some(false)` if it stopped, or `None` if it didn't change.
+    pub fn buzzer_edge(&self) -> Option<bool> {
+        self.buzzer_edge
+    }
+
+    /// Frequency in Hz at which the audio pattern's bits are played back, set by Fx3A.
+    /// The host should play the pattern at this rate while `buzzer_active` is true.
+    pub fn playback_frequency(&self) -> f32 {
+        4000.0 * libm::powf(2.0, (self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// Freezes execution and timers until `resume` is called.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Whether execution has stopped, after an error, a detected self-jump, a 0000 opcode
+    /// under `set_halt_on_zero` or a SYS call under `SysPolicy::Halt`.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// When enabled, a 1nnn that jumps to itself halts the interpreter instead of spinning
+    /// forever. Off by default, as some ROMs busy-wait this way on purpose.
+    pub fn set_detect_halt(&mut self, enabled: bool) {
+        self.detect_halt = enabled;
+    }
+
+    /// When enabled, a 0000 opcode halts the interpreter with the pc left on it, so a ROM
+    /// that runs off its end stops instead of spinning through zeroed memory. Other 0nnn
+    /// opcodes are still ignored.
+    pub fn set_halt_on_zero(&mut self, enabled: bool) {
+        self.halt_on_zero = enabled;
+    }
+
+    /// Creates an interpreter whose Cxkk results are determined by `seed`.
+    pub fn with_seed(clock_freq: u32, seed: u64) -> Self {
+        let mut state = Self::new(clock_freq);
+        state.reseed(seed);
+        state
+    }
+
+    /// Fills the registers and memory, apart from the fonts and any loaded ROM, according to
+    /// `init`. Works with any quirks, e.g. after `with_quirks` and before `load`.
+    pub fn set_memory_init(&mut self, init: MemoryInit) {
+        let font = self.font_offset as usize..self.font_offset as usize + font::FONT.len();
+        let big_font = 0xA0..0xA0 + font::BIG_FONT.len();
+        let rom = self.start as usize..self.start as usize + self.rom_len;
+        let next = |rng: &mut SplitMix64| match init {
+            MemoryInit::Zero => 0,
+            MemoryInit::Pattern(byte) => byte,
+            MemoryInit::Random => rng.next_u8(),
+        };
+
+        for register in self.registers.iter_mut() {
+            *register = next(&mut self.rng);
+        }
+        for (addr, cell) in self.memory.iter_mut().enumerate() {
+            if !font.contains(&addr) && !big_font.contains(&addr) && !rom.contains(&addr) {
+                *cell = next(&mut self.rng);
+            }
+        }
+    }
+
+    /// Sets the rate the delay and sound timers count down at, 60Hz by default, falling back
+    /// to 60Hz for an invalid 0Hz rate.
+    pub fn set_timer_frequency(&mut self, timer_freq: u32) {
+        self.timer_period = if timer_freq == 0 {
+            log::warn!("Timer frequency of 0Hz is invalid, using 60Hz");
+            TIMER_PERIOD
+        } else {
+            // Very high rates would round to a zero period, which tick_timers divides by
+            Duration::from_secs_f64(1_f64 / timer_freq as f64).max(Duration::from_nanos(1))
+        };
+    }
+
+    /// Makes room for `depth` nested calls instead of the usual 16. Returns false, leaving the
+    /// stack alone, if more calls than that are already in progress.
+    pub fn set_stack_depth(&mut self, depth: u8) -> bool {
+        if self.pointer > depth {
+            return false;
+        }
+        self.stack.resize(depth as usize, 0);
+        true
+    }
+
+    /// Replaces the quirks, e.g. once the platform of a loaded ROM is known. Turning off
+    /// `extended_memory` wraps pc and I back into 4K.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        self.pc &= self.addr_mask();
+        self.index &= self.addr_mask();
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = SplitMix64::new(seed);
+    }
+
+    /// Changes the clock frequency at runtime, e.g. to fast-forward.
+    pub fn set_frequency(&mut self, clock_freq: u32) {
+        self.speed = clock_speed(clock_freq);
+    }
+
+    pub fn frequency(&self) -> u32 {
+        libm::round(1_f64 / self.speed.as_secs_f64()) as u32
+    }
+
+    /// Returns the machine to its power-on state, keeping the loaded ROM and fonts in memory.
+    /// The SUPER-CHIP flags survive; use `reset_with` to clear them too.
+    pub fn reset(&mut self) {
+        self.reset_with(ResetPolicy::Soft);
+    }
+
+    /// Resets like `reset`, clearing the SUPER-CHIP flags as well under `ResetPolicy::Hard`.
+    pub fn reset_with(&mut self, policy: ResetPolicy) {
+        if policy == ResetPolicy::Hard {
+            self.flags = [0; 8];
+        }
+        self.registers = [0; 16];
+        self.pc = self.start;
+        self.index = 0;
+        self.pointer = 0;
+        self.stack.fill(0);
+        self.display = [[[Pixel::default(); 128]; 64]; 2];
+        self.overlay = [[Pixel::default(); 128]; 64];
+        self.hires = false;
+        self.planes = 0b01;
+        self.mark_all_dirty();
+        self.refresh_frame();
+        self.ticker = Duration::ZERO;
+        self.locked_steps = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.audio_pattern = None;
+        self.pitch = 64;
+        self.waiting_for_vblank = false;
+        self.waiting_for_key = None;
+        self.key_held = None;
+        self.halted = false;
+        self.breakpoint_hit = None;
+        self.history.clear();
+    }
+
+    /// Loads the ROM at `start` (0x200 on most machines, 0x600 on the ETI-660) and jumps to it.
+    #[cfg(feature = "std")]
+    pub fn load(&mut self, rom: PathBuf, start: u16) -> Result<(), Chip8Error> {
+        let bytes = std::fs::read(rom)?;
+        self.load_bytes(&bytes, start)
+    }
+
+    /// Reads a ROM to the end of `reader` and loads it like `load`.
+    #[cfg(feature = "std")]
+    pub fn load_reader<R: Read>(&mut self, mut reader: R, start: u16) -> Result<(), Chip8Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        self.load_bytes(&bytes, start)
+    }
+
+    /// Loads one of the ROMs bundled in `crate::roms` at 0x200.
+    pub fn load_embedded(&mut self, name: &str) -> Result<(), Chip8Error> {
+        let bytes = crate::roms::find(name).ok_or_else(|| Chip8Error::UnknownRom(name.into()))?;
+        self.load_bytes(bytes, 0x200)
+    }
+
+    /// Copies a ROM already in memory to `start` and jumps to it, rejecting empty ROMs and
+    /// ROMs that don't fit.
+    pub fn load_bytes(&mut self, bytes: &[u8], start: u16) -> Result<(), Chip8Error> {
+        let start = start as usize;
+        if start >= self.memory_size() {
+            return Err(Chip8Error::MemoryOutOfBounds { addr: start });
+        }
+        let capacity = self.memory_size() - start;
+
+        if bytes.is_empty() {
+            return Err(Chip8Error::EmptyRom);
+        }
+        if bytes.len() > capacity {
+            return Err(Chip8Error::RomTooLarge {
+                size: bytes.len(),
+                capacity,
+            });
+        }
+
+        self.memory[start..start + bytes.len()].copy_from_slice(bytes);
+        self.start = start as u16;
+        self.rom_len = bytes.len();
+        self.rom_crc32 = crc32(bytes);
+        self.code_end = start;
+        self.pc = self.start;
+        Ok(())
+    }
+
+    fn fetch(&mut self) -> u16 {
+        let instruction = self.peek();
         self.increment_pc();
         instruction
     }
 
-    fn execute(&mut self, instruction: Instruction, keys: &Keys) -> Option<Display> {
+    /// The opcode at pc, without moving past it.
+    fn peek(&self) -> u16 {
+        u16::from_be_bytes([
+            self.memory[self.mem_addr(self.pc as usize)],
+            self.memory[self.mem_addr(self.pc as usize + 1)],
+        ])
+    }
+
+    /// Skips the next instruction, including the address word of an F000 NNNN (XO-CHIP).
+    fn skip(&mut self) {
+        let long = self.peek() == 0xF000;
+        self.increment_pc();
+        if long {
+            self.increment_pc();
+        }
+    }
+
+    /// Executes `instruction`, returning whether it changed the display.
+    fn execute(&mut self, instruction: Instruction, keys: &Keys) -> Result<bool, Chip8Error> {
         match instruction {
             Instruction::Nop => (),
             Instruction::Cls => {
-                self.display = [[Pixel::default(); 64]; 32];
-                return Some(self.display);
+                // Nothing changes if the selected planes are already clear, so skip the redraw
+                let already_clear = self.selected_planes().all(|plane| {
+                    self.display[plane]
+                        .iter()
+                        .flatten()
+                        .all(|&pixel| !bool::from(pixel))
+                });
+                if already_clear {
+                    return Ok(false);
+                }
+                self.mark_selected_planes_dirty();
+                for plane in self.selected_planes() {
+                    self.display[plane] = [[Pixel::default(); 128]; 64];
+                }
+                return Ok(true);
+            }
+            Instruction::ScrollDown(n) => {
+                self.mark_selected_planes_dirty();
+                let height = self.height();
+                for plane in self.selected_planes() {
+                    let display = &mut self.display[plane];
+                    for y in (0..height).rev() {
+                        display[y] = match y.checked_sub(n as usize) {
+                            Some(src) => display[src],
+                            None => [Pixel::default(); 128],
+                        };
+                    }
+                }
+                return Ok(true);
+            }
+            Instruction::ScrollRight => {
+                self.mark_selected_planes_dirty();
+                let (width, height) = (self.width(), self.height());
+                for plane in self.selected_planes() {
+                    for row in self.display[plane].iter_mut().take(height) {
+                        row.copy_within(0..width - 4, 4);
+                        row[..4].fill(Pixel::default());
+                    }
+                }
+                return Ok(true);
+            }
+            Instruction::ScrollLeft => {
+                self.mark_selected_planes_dirty();
+                let (width, height) = (self.width(), self.height());
+                for plane in self.selected_planes() {
+                    for row in self.display[plane].iter_mut().take(height) {
+                        row.copy_within(4..width, 0);
+                        row[width - 4..width].fill(Pixel::default());
+                    }
+                }
+                return Ok(true);
+            }
+            Instruction::Lores | Instruction::Hires => {
+                self.hires = matches!(instruction, Instruction::Hires);
+                self.display = [[[Pixel::default(); 128]; 64]; 2];
+                self.mark_all_dirty();
+                return Ok(true);
+            }
+            Instruction::Plane(n) => self.planes = n & 0b11,
+            Instruction::StoreAudio => {
+                let mut pattern = [0; 16];
+                for (i, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.memory[self.mem_addr(self.index as usize + i)];
+                }
+                self.audio_pattern = Some(pattern);
             }
             Instruction::Ret => {
-                self.pc = self.stack[self.pointer as usize];
+                // pointer is the stack depth, so the top entry is at pointer - 1
+                if self.pointer == 0 {
+                    return Err(Chip8Error::StackUnderflow { pc: self.pc });
+                }
                 self.pointer -= 1;
+                self.pc = self.stack[self.pointer as usize];
+            }
+            Instruction::Jmp(addr) => {
+                // pc has already moved past the jump
+                if self.detect_halt && addr == self.pc.wrapping_sub(2) & self.addr_mask() {
+                    log::info!("Jump to self at {:#05X}, halting interpreter", addr);
+                    self.halted = true;
+                }
+                self.pc = addr;
             }
-            Instruction::Jmp(addr) => self.pc = addr,
             Instruction::Call(addr) => {
-                self.pointer += 1;
+                if self.pointer as usize >= self.stack.len() {
+                    return Err(Chip8Error::StackOverflow { pc: self.pc });
+                }
                 self.stack[self.pointer as usize] = self.pc;
+                self.pointer += 1;
                 self.pc = addr;
             }
             Instruction::Ske(x, byte) => {
                 if self.registers[x as usize] == byte {
-                    self.increment_pc();
+                    self.skip();
                 }
             }
             Instruction::Skne(x, byte) => {
                 if self.registers[x as usize] != byte {
-                    self.increment_pc();
+                    self.skip();
                 }
             }
             Instruction::Skre(x, y) => {
                 if self.registers[x as usize] == self.registers[y as usize] {
-                    self.increment_pc();
+                    self.skip();
+                }
+            }
+            // I is left unchanged, and x > y transfers the registers in descending order
+            Instruction::SaveRange(x, y) => {
+                if self.is_protected(x.abs_diff(y) as usize + 1) {
+                    log::warn!(
+                        "5xy2 would overwrite reserved memory at {:#05X}",
+                        self.index
+                    );
+                    return Ok(false);
+                }
+                self.check_code_write(x.abs_diff(y) as usize + 1)?;
+                for i in 0..=x.abs_diff(y) as usize {
+                    let addr = self.mem_addr(self.index as usize + i);
+                    self.memory[addr] = self.registers[range_register(x, y, i)];
+                }
+            }
+            Instruction::LoadRange(x, y) => {
+                for i in 0..=x.abs_diff(y) as usize {
+                    let addr = self.mem_addr(self.index as usize + i);
+                    self.registers[range_register(x, y, i)] = self.memory[addr];
                 }
             }
             Instruction::Setr(x, byte) => self.registers[x as usize] = byte,
+            // 7xkk never sets a carry flag, on any platform or quirk. 7Fkk treats VF as an
+            // ordinary register and stores the wrapped sum in it.
             Instruction::Addr(x, byte) => {
                 self.registers[x as usize] = self.registers[x as usize].wrapping_add(byte);
             }
             Instruction::Move(x, y) => self.registers[x as usize] = self.registers[y as usize],
-            Instruction::Or(x, y) => self.registers[x as usize] |= self.registers[y as usize],
-            Instruction::And(x, y) => self.registers[x as usize] &= self.registers[y as usize],
-            Instruction::Xor(x, y) => self.registers[x as usize] ^= self.registers[y as usize],
+            Instruction::Or(x, y) => {
+                self.registers[x as usize] |= self.registers[y as usize];
+                self.logic_vf_reset();
+            }
+            Instruction::And(x, y) => {
+                self.registers[x as usize] &= self.registers[y as usize];
+                self.logic_vf_reset();
+            }
+            Instruction::Xor(x, y) => {
+                self.registers[x as usize] ^= self.registers[y as usize];
+                self.logic_vf_reset();
+            }
+            // The flag instructions below always write VF last, so if x is 0xF
+            // the arithmetic result is discarded and only the flag survives.
             Instruction::Add(x, y) => {
                 let (value, carry) =
                     self.registers[x as usize].overflowing_add(self.registers[y as usize]);
@@ -142,9 +1408,12 @@ impl ChipState {
                 self.registers[0xF] = if borrow { 0 } else { 1 };
             }
             Instruction::Shr(x, y) => {
-                self.registers[0xF] = self.registers[x as usize] & 0b1;
+                // Copy Vy first so the flag comes from the value actually shifted, and
+                // write VF last so it wins when x is F. Both are no-ops for x == y.
+                self.shift_source(x, y);
+                let flag = self.registers[x as usize] & 0b1;
                 self.registers[x as usize] >>= 1;
-                log::trace!("The y value {} was ignored - not used in this version", y);
+                self.registers[0xF] = flag;
             }
             Instruction::Ssub(x, y) => {
                 let (value, borrow) =
@@ -153,113 +1422,483 @@ impl ChipState {
                 self.registers[0xF] = if borrow { 0 } else { 1 };
             }
             Instruction::Shl(x, y) => {
-                self.registers[0xF] = (self.registers[x as usize] & 0x80) >> 7;
+                self.shift_source(x, y);
+                let flag = (self.registers[x as usize] & 0x80) >> 7;
                 self.registers[x as usize] <<= 1;
-                log::trace!("The y value {} was ignored - not used in this version", y);
+                self.registers[0xF] = flag;
             }
             Instruction::Skrne(x, y) => {
                 if self.registers[x as usize] != self.registers[y as usize] {
-                    self.increment_pc();
+                    self.skip();
                 }
             }
             Instruction::Seti(addr) => self.index = addr,
-            Instruction::Jmpr(addr) => self.pc = (addr + self.registers[0] as u16) & 0x0FFF,
-            Instruction::Rand(x, byte) => self.registers[x as usize] = random::<u8>() & byte,
+            Instruction::LoadILong(addr) => self.index = addr & self.addr_mask(),
+            Instruction::Jmpr(x, addr) => {
+                let offset = if self.quirks.jump_uses_vx {
+                    self.registers[x as usize]
+                } else {
+                    self.registers[0]
+                };
+                // SUPER-CHIP and XO-CHIP programs can jump past 0xFFF into extended memory
+                self.pc = addr.wrapping_add(offset as u16) & self.addr_mask();
+            }
+            Instruction::Rand(x, byte) => self.registers[x as usize] = self.rng.next_u8() & byte,
             Instruction::Draw(vx, vy, n) => {
-                self.registers[0xF] = 0;
-                let n = n.min(15);
-
-                let sprite: Vec<Vec<Pixel>> = self
-                    .memory
-                    .iter()
-                    .skip(self.index as usize)
-                    .take(n as usize)
-                    .cloned()
-                    .map(|byte| {
-                        (0..u8::BITS)
-                            .map(move |i| Pixel::try_from(byte >> i & 0b1).unwrap_or_default())
-                            .rev()
-                            .collect()
-                    })
-                    .collect();
-
-                for (i, row) in sprite.iter().enumerate() {
-                    let y = (self.registers[vy as usize] % 32) + i as u8;
-
-                    if y >= 32 {
-                        break;
+                if self.quirks.display_wait {
+                    if self.waiting_for_vblank {
+                        // Re-queue the draw until the next timer tick
+                        self.decrement_pc();
+                        self.draw_deferred = true;
+                        return Ok(false);
                     }
+                    self.waiting_for_vblank = true;
+                }
+
+                // Dxy0 in hires mode draws a 16x16 sprite, two bytes per row (SUPER-CHIP)
+                let (rows, row_bytes) = if n == 0 && self.hires {
+                    (16, 2)
+                } else {
+                    (n.min(15) as usize, 1)
+                };
 
-                    for (j, bit) in row.iter().enumerate() {
-                        let x = (self.registers[vx as usize] % 64) + j as u8;
+                // The origin wraps at the active resolution, 64x32 or 128x64 in hires mode,
+                // before the sprite itself is wrapped or clipped
+                let (width, height) = (self.width(), self.height());
+                let sprite_len = rows * row_bytes;
+                let (mut collided_rows, mut clipped_rows) = (0, 0);
 
-                        if x >= 64 {
+                // With several planes selected, each plane takes the next sprite's worth
+                // of bytes in turn (XO-CHIP)
+                for (n, plane) in self.selected_planes().enumerate() {
+                    let base = self.index as usize + n * sprite_len;
+
+                    for i in 0..rows {
+                        let mut y = (self.registers[vy as usize] as usize % height) + i;
+
+                        if self.quirks.sprite_wrapping {
+                            y %= height;
+                        } else if y >= height {
+                            clipped_rows += rows - i;
                             break;
                         }
 
-                        let pixel = &mut self.display[y as usize][x as usize];
-                        // Set VF if pixel was erased, then set pixel on display
-                        if (*pixel & *bit).into() {
-                            self.registers[0xF] = 1;
+                        let mut collided = false;
+
+                        for j in 0..row_bytes * u8::BITS as usize {
+                            // Sprite data past the end of memory wraps or clamps like any other read
+                            let addr = self.mem_addr(base + i * row_bytes + j / 8);
+                            let shift = match self.quirks.sprite_bit_order {
+                                BitOrder::MsbFirst => 7 - j % 8,
+                                BitOrder::LsbFirst => j % 8,
+                            };
+                            let bit = Pixel::try_from(self.memory[addr] >> shift & 0b1)
+                                .unwrap_or_default();
+
+                            let mut x = (self.registers[vx as usize] as usize % width) + j;
+
+                            if self.quirks.sprite_wrapping {
+                                x %= width;
+                            } else if x >= width {
+                                break;
+                            }
+
+                            let pixel = &mut self.display[plane][y][x];
+                            // Note if pixel was erased, then set pixel on display
+                            if (*pixel & bit).into() {
+                                collided = true;
+                            }
+                            *pixel ^= bit;
+                            if bit.into() {
+                                self.mark_dirty(DisplayDiff::pixel(x, y));
+                            }
+                        }
+
+                        if collided {
+                            collided_rows += 1;
                         }
-                        *pixel ^= *bit;
                     }
                 }
 
-                return Some(self.display);
+                let count_rows = self.quirks.sprite_clip_counts_collisions
+                    || (self.quirks.hires_collision_count && self.hires);
+                let vf = if count_rows {
+                    (collided_rows + clipped_rows).min(u8::MAX as usize) as u8
+                } else {
+                    (collided_rows > 0) as u8
+                };
+                if vf != 0 || self.quirks.draw_vf_reset {
+                    self.registers[0xF] = vf;
+                }
+
+                return Ok(true);
             }
             Instruction::Skp(x) => {
-                if keys[self.registers[x as usize] as usize] {
-                    self.increment_pc();
+                if self.key_down(keys, self.registers[x as usize]) {
+                    self.skip();
                 }
             }
             Instruction::Sknp(x) => {
-                if !keys[self.registers[x as usize] as usize] {
-                    self.increment_pc();
+                if !self.key_down(keys, self.registers[x as usize]) {
+                    self.skip();
                 }
             }
             Instruction::Moved(x) => self.registers[x as usize] = self.delay_timer,
             Instruction::Key(x) => {
-                if keys.iter().all(|k| !k) {
-                    self.pc -= 2;
-                } else {
-                    self.registers[x as usize] = keys.iter().position(|&key| key).unwrap() as u8;
-                    log::debug!("Key {:?} was pressed", self.registers[x as usize]);
-                }
+                self.waiting_for_key = Some(x);
+                self.poll_key(keys);
             }
             Instruction::Setrd(x) => self.delay_timer = self.registers[x as usize],
             Instruction::Setrs(x) => self.sound_timer = self.registers[x as usize],
+            // I wraps at the end of addressable memory
             Instruction::Addi(x) => {
-                self.index += self.registers[x as usize] as u16;
-                self.index &= 0x0FFF;
+                let sum = self.index as u32 + self.registers[x as usize] as u32;
+                self.index = sum as u16 & self.addr_mask();
+                if self.quirks.index_overflow_sets_vf {
+                    self.registers[0xF] = (sum > self.addr_mask() as u32) as u8;
+                }
             }
-            Instruction::Ldfnt(x) => self.index = 0x50 + (5 * self.registers[x as usize] as u16),
+            Instruction::Ldfnt(x) => {
+                // Only the low nibble selects a digit, so the sprite stays inside the font
+                let digit = self.registers[x as usize] & 0xF;
+                self.index = self.font_offset + 5 * digit as u16;
+            }
+            Instruction::Ldbigfnt(x) => {
+                self.index = 0xA0 + (10 * (self.registers[x as usize] % 10) as u16);
+            }
+            Instruction::SetPitch(x) => self.pitch = self.registers[x as usize],
             Instruction::Bcd(x) => {
-                let mem_slice = &mut self.memory[(self.index as usize)..(self.index as usize + 3)];
-
-                mem_slice[0] = self.registers[x as usize] / 100;
-                mem_slice[1] = self.registers[x as usize] % 100 / 10;
-                mem_slice[2] = self.registers[x as usize] % 10;
+                if self.is_protected(3) {
+                    log::warn!(
+                        "Fx33 would overwrite reserved memory at {:#05X}",
+                        self.index
+                    );
+                    return Ok(false);
+                }
+                self.check_code_write(3)?;
+                let value = self.registers[x as usize];
+                // Digits past the end of memory wrap or clamp like any other address
+                for (i, digit) in [value / 100, value % 100 / 10, value % 10]
+                    .into_iter()
+                    .enumerate()
+                {
+                    let addr = self.mem_addr(self.index as usize + i);
+                    self.memory[addr] = digit;
+                }
             }
             Instruction::Store(x) => {
+                if self.is_protected(x as usize + 1) {
+                    log::warn!(
+                        "Fx55 would overwrite reserved memory at {:#05X}",
+                        self.index
+                    );
+                    self.advance_index(x);
+                    return Ok(false);
+                }
+                self.check_code_write(x as usize + 1)?;
+                // Registers past the end of memory wrap or clamp like any other address
                 for r in 0..=x as usize {
-                    self.memory[self.index as usize + r] = self.registers[r];
+                    let addr = self.mem_addr(self.index as usize + r);
+                    self.memory[addr] = self.registers[r];
                 }
+                self.advance_index(x);
             }
             Instruction::Load(x) => {
                 for r in 0..=x as usize {
-                    self.registers[r] = self.memory[self.index as usize + r];
+                    self.registers[r] = self.memory[self.mem_addr(self.index as usize + r)];
                 }
+                self.advance_index(x);
+            }
+            // The HP-48 only has 8 flag registers
+            Instruction::StoreFlags(x) => {
+                let n = x.min(7) as usize + 1;
+                self.flags[..n].copy_from_slice(&self.registers[..n]);
             }
+            Instruction::LoadFlags(x) => {
+                let n = x.min(7) as usize + 1;
+                self.registers[..n].copy_from_slice(&self.flags[..n]);
+            }
+        };
+
+        Ok(false)
+    }
+
+    fn width(&self) -> usize {
+        if self.hires {
+            128
+        } else {
+            64
+        }
+    }
+
+    fn height(&self) -> usize {
+        if self.hires {
+            64
+        } else {
+            32
+        }
+    }
+
+    fn mark_dirty(&mut self, region: DisplayDiff) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(region),
+            None => region,
+        });
+    }
+
+    /// Cls and scrolling change the display only if a selected plane has something on it.
+    fn mark_selected_planes_dirty(&mut self) {
+        let lit = self.selected_planes().any(|plane| {
+            self.display[plane]
+                .iter()
+                .flatten()
+                .any(|&pixel| pixel.into())
+        });
+        if lit {
+            self.mark_all_dirty();
+        }
+    }
+
+    fn mark_all_dirty(&mut self) {
+        self.mark_dirty(DisplayDiff {
+            x: 0,
+            y: 0,
+            width: self.width(),
+            height: self.height(),
+        });
+    }
+
+    fn selected_planes(&self) -> impl Iterator<Item = usize> {
+        let planes = self.planes;
+        (0..self.display.len()).filter(move |plane| planes >> plane & 0b1 == 1)
+    }
+
+    fn refresh_frame(&mut self) {
+        self.frame = self.build_frame();
+        self.frame_stale = false;
+    }
+
+    /// Builds the 64x32 frame handed to chip8_base from the active display area.
+    /// In hires mode each output pixel covers a 2x2 block, collapsed by the `DownscaleMode`.
+    /// Colours are approximated in monochrome: plane 0 is lit, plane 1 alone is a checkerboard.
+    /// The overlay is composited in as if it were on plane 0.
+    fn build_frame(&self) -> Display {
+        let mut frame = [[Pixel::default(); 64]; 32];
+        let colour_at = |py: usize, px: usize| {
+            let low: bool = self.display[0][py][px].into();
+            let high: bool = self.display[1][py][px].into();
+            let overlay: bool = self.overlay[py][px].into();
+            (low | overlay) as u8 | (high as u8) << 1
         };
 
-        None
+        for (y, row) in frame.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let colour = if self.hires {
+                    let (py, px) = (y * 2, x * 2);
+                    self.downscale.collapse([
+                        colour_at(py, px),
+                        colour_at(py, px + 1),
+                        colour_at(py + 1, px),
+                        colour_at(py + 1, px + 1),
+                    ])
+                } else {
+                    colour_at(y, x)
+                };
+
+                let lit = match colour {
+                    0 => false,
+                    2 => (x + y) % 2 == 0,
+                    _ => true,
+                };
+                *pixel = Pixel::try_from(lit as u8).unwrap_or_default();
+            }
+        }
+
+        frame
+    }
+
+    /// Emulated time taken to execute `instruction` under the timing model.
+    fn instruction_time(&self, instruction: &Instruction) -> Duration {
+        match self.timing_model {
+            TimingModel::Flat => self.speed,
+            TimingModel::Vip => timing::VIP_CYCLE * cycle_cost(instruction),
+        }
+    }
+
+    /// Ticks the timers once for every timer period of emulated time elapsed.
+    fn tick_timers(&mut self, elapsed: Duration) {
+        // step_frame ticks the timers itself
+        if self.frame_batching {
+            return;
+        }
+        match self.timer_mode {
+            TimerMode::Duration => {
+                self.ticker += elapsed;
+                let ticks = (self.ticker.as_nanos() / self.timer_period.as_nanos()) as u32;
+                if ticks > 0 {
+                    self.ticker -= self.timer_period * ticks;
+                    self.apply_ticks(ticks.min(u8::MAX as u32) as u8);
+                }
+            }
+            TimerMode::CycleLocked => {
+                let steps_per_tick =
+                    libm::round(self.timer_period.as_nanos() as f64 / self.speed.as_nanos() as f64);
+                self.locked_steps += 1;
+                if self.locked_steps as f64 >= steps_per_tick.max(1.0) {
+                    self.locked_steps = 0;
+                    self.apply_ticks(1);
+                }
+            }
+        }
+    }
+
+    fn apply_ticks(&mut self, ticks: u8) {
+        self.delay_timer = self.delay_timer.saturating_sub(ticks);
+        self.sound_timer = self.sound_timer.saturating_sub(ticks);
+        self.waiting_for_vblank = false;
+        self.ticked = true;
+        self.decay_intensity(ticks);
+    }
+
+    /// Fully lights every lit pixel and fades the rest by `phosphor_decay` per tick.
+    fn decay_intensity(&mut self, ticks: u8) {
+        let fade = self.phosphor_decay.saturating_mul(ticks);
+        // Everything unlit goes straight to 0, and render_intensity lights the rest
+        if fade == u8::MAX {
+            self.intensity = [[0; 64]; 32];
+            return;
+        }
+
+        let frame = self.current_frame();
+        for (intensity_row, frame_row) in self.intensity.iter_mut().zip(frame.iter()) {
+            for (intensity, &pixel) in intensity_row.iter_mut().zip(frame_row.iter()) {
+                *intensity = if pixel.into() {
+                    u8::MAX
+                } else {
+                    intensity.saturating_sub(fade)
+                };
+            }
+        }
+    }
+
+    /// The frame as the display is now, built only if `step_compute` has left it stale.
+    fn current_frame(&self) -> Display {
+        if self.frame_stale {
+            self.build_frame()
+        } else {
+            self.frame
+        }
+    }
+
+    /// Completes a pending Fx0A once a pressed key is released, so a held key only registers once.
+    fn poll_key(&mut self, keys: &Keys) {
+        if let Some(x) = self.waiting_for_key {
+            match self.key_held {
+                Some(key) if !self.key_down(keys, key) => {
+                    self.registers[x as usize] = key;
+                    self.key_held = None;
+                    self.waiting_for_key = None;
+                    log::debug!("Key {:?} was pressed", key);
+                }
+                Some(_) => (),
+                None => self.key_held = (0..16).find(|&key| self.key_down(keys, key)),
+            }
+        }
+    }
+
+    fn key_down(&self, keys: &Keys, key: u8) -> bool {
+        let slot = self.keymap[key as usize & 0xF];
+        keys.get(slot).copied().unwrap_or(false)
+    }
+
+    fn shift_source(&mut self, x: u8, y: u8) {
+        if self.quirks.shift_uses_vy {
+            self.registers[x as usize] = self.registers[y as usize];
+        } else {
+            log::trace!("The y value {} was ignored - not used in this version", y);
+        }
+    }
+
+    fn advance_index(&mut self, x: u8) {
+        if self.quirks.memory_increments_index {
+            let step = if self.quirks.memory_increment_by_x {
+                x as u16
+            } else {
+                x as u16 + 1
+            };
+            self.index = self.index.wrapping_add(step) & self.addr_mask();
+        }
+    }
+
+    /// Bytes of memory addressable in the current mode: 4K, or 64K with extended memory.
+    fn memory_size(&self) -> usize {
+        self.addr_mask() as usize + 1
+    }
+
+    fn addr_mask(&self) -> u16 {
+        if self.quirks.extended_memory {
+            0xFFFF
+        } else {
+            0x0FFF
+        }
+    }
+
+    /// Clears VF after 8xy1/8xy2/8xy3 under `logic_quirk`.
+    fn logic_vf_reset(&mut self) {
+        if self.quirks.logic_quirk {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    /// Checks a write of `len` bytes at I against the code that has run so far, reporting it
+    /// under the `CodeWritePolicy`.
+    fn check_code_write(&mut self, len: usize) -> Result<(), Chip8Error> {
+        if self.code_write_policy == CodeWritePolicy::Allow {
+            return Ok(());
+        }
+        let code = self.start as usize..self.code_end;
+        let Some(addr) = self.write_targets(len).find(|addr| code.contains(addr)) else {
+            return Ok(());
+        };
+
+        let addr = addr as u16;
+        // pc has already moved past the write
+        let pc = self.pc.wrapping_sub(2) & self.addr_mask();
+        if self.code_write_policy == CodeWritePolicy::Error {
+            return Err(Chip8Error::CodeWrite { pc, addr });
+        }
+        log::warn!("{}", Chip8Error::CodeWrite { pc, addr });
+        self.last_code_write = Some(addr);
+        Ok(())
+    }
+
+    /// Whether a write of `len` bytes at I should be refused under `protect_reserved`.
+    fn is_protected(&self, len: usize) -> bool {
+        self.protect_reserved && self.write_targets(len).any(|addr| addr < 0x200)
+    }
+
+    /// The addresses a write of `len` bytes at I lands on, after wrapping or clamping.
+    fn write_targets(&self, len: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..len).map(move |i| self.mem_addr(self.index as usize + i))
+    }
+
+    /// Maps an address that may run past the end of memory back into range.
+    fn mem_addr(&self, addr: usize) -> usize {
+        if self.quirks.clamp_addresses {
+            addr.min(self.memory_size() - 1)
+        } else {
+            addr % self.memory_size()
+        }
+    }
+
+    fn decrement_pc(&mut self) {
+        self.pc = self.pc.wrapping_sub(2) & self.addr_mask();
     }
 
     fn increment_pc(&mut self) {
-        self.pc += 2;
-        // Reset PC to 0 when memory address 4096 is reached.
-        // This mask works as addr is 12-bit but PC is 16-bit.
-        self.pc &= 0x0FFF;
+        self.pc = self.pc.wrapping_add(2);
+        // Reset PC to 0 when the end of memory is reached.
+        // This mask works as addr is 12-bit (16-bit with extended memory) but PC is 16-bit.
+        self.pc &= self.addr_mask();
     }
 }
@@ -0,0 +1,88 @@
+use super::{Instruction, Quirks};
+
+/// A family of CHIP-8 implementations that share a set of quirks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// CHIP-8 as most modern interpreters run it, with the default quirks
+    Chip8,
+    /// The original COSMAC VIP interpreter
+    Vip,
+    /// CHIP-48 on the HP 48, which SUPER-CHIP grew out of
+    Chip48,
+    /// SUPER-CHIP 1.1 on the HP 48
+    SuperChip,
+    /// Octo's XO-CHIP extensions
+    XoChip,
+}
+
+impl Platform {
+    /// The quirks a ROM written for this platform is likely to expect.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::default(),
+            Platform::Vip => Quirks {
+                shift_uses_vy: true,
+                display_wait: true,
+                memory_increments_index: true,
+                logic_quirk: true,
+                ..Quirks::default()
+            },
+            Platform::Chip48 => Quirks {
+                memory_increments_index: true,
+                memory_increment_by_x: true,
+                jump_uses_vx: true,
+                ..Quirks::default()
+            },
+            Platform::SuperChip => Quirks {
+                jump_uses_vx: true,
+                hires_collision_count: true,
+                ..Quirks::default()
+            },
+            Platform::XoChip => Quirks {
+                shift_uses_vy: true,
+                memory_increments_index: true,
+                sprite_wrapping: true,
+                extended_memory: true,
+                ..Quirks::default()
+            },
+        }
+    }
+}
+
+/// Guesses which platform a ROM targets from the opcodes it contains.
+///
+/// Only word-aligned opcodes are inspected, and sprite data can happen to look like an
+/// extended opcode, so this can both miss extensions and report ones that aren't used.
+/// ROMs that stick to the base instruction set are reported as the modern `Chip8`, even if
+/// they rely on the quirks of another platform such as the `Vip`.
+pub fn detect_platform(bytes: &[u8]) -> Platform {
+    let mut platform = Platform::Chip8;
+
+    for (i, word) in bytes.chunks_exact(2).enumerate() {
+        let opcode = u16::from_be_bytes([word[0], word[1]]);
+        match Instruction::decode(opcode, 0x200 + 2 * i as u16) {
+            Ok(
+                Instruction::LoadILong(_)
+                | Instruction::Plane(_)
+                | Instruction::StoreAudio
+                | Instruction::SaveRange(..)
+                | Instruction::LoadRange(..),
+            ) => {
+                return Platform::XoChip;
+            }
+            Ok(
+                Instruction::Hires
+                | Instruction::Lores
+                | Instruction::ScrollDown(_)
+                | Instruction::ScrollRight
+                | Instruction::ScrollLeft
+                | Instruction::Ldbigfnt(_)
+                | Instruction::StoreFlags(_)
+                | Instruction::LoadFlags(_),
+            ) => platform = Platform::SuperChip,
+            _ => (),
+        }
+    }
+
+    platform
+}
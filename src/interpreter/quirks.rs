@@ -0,0 +1,70 @@
+/// Behaviour that differs between CHIP-8 implementations.
+///
+/// The default matches the behaviour this interpreter has always had.
+#[derive(Debug, Clone, Copy)]
+pub struct Quirks {
+    /// 8xy6/8xyE copy Vy into Vx before shifting (COSMAC VIP), rather than shifting Vx in place.
+    pub shift_uses_vy: bool,
+    /// Dxyn waits for the next 60Hz vertical blank, so at most one sprite is drawn per frame (COSMAC VIP).
+    pub display_wait: bool,
+    /// Addresses past the end of memory clamp to the last byte rather than wrapping to 0x000.
+    /// The COSMAC VIP, CHIP-48, SUPER-CHIP and XO-CHIP all wrap, so no `Platform` preset sets
+    /// this; it matches ports that bounds-check memory accesses instead of masking them.
+    pub clamp_addresses: bool,
+    /// Fx55/Fx65 leave I pointing past the last register transferred, at I + x + 1 (COSMAC VIP).
+    pub memory_increments_index: bool,
+    /// With `memory_increments_index`, I is advanced by x instead of x + 1 (CHIP-48).
+    pub memory_increment_by_x: bool,
+    /// Bnnn is read as Bxnn and jumps to xnn + Vx instead of nnn + V0 (CHIP-48, SUPER-CHIP).
+    pub jump_uses_vx: bool,
+    /// Fx1E sets VF to 1 when I + Vx runs past the end of memory, and to 0 otherwise (Amiga).
+    pub index_overflow_sets_vf: bool,
+    /// Sprites that run off one edge of the screen wrap around to the opposite edge instead of being clipped.
+    pub sprite_wrapping: bool,
+    /// VF is set to the number of sprite rows that collided or were clipped off the bottom of
+    /// the screen, rather than 1 for any collision (SUPER-CHIP).
+    pub sprite_clip_counts_collisions: bool,
+    /// Like `sprite_clip_counts_collisions`, but only in hires mode, with lores draws still
+    /// setting VF to 1 for any collision (SUPER-CHIP 1.1).
+    pub hires_collision_count: bool,
+    /// Memory is 64K rather than 4K, so I and PC are 16-bit and F000 NNNN can load I (XO-CHIP).
+    pub extended_memory: bool,
+    /// Dxyn always writes VF, clearing it when nothing collided. When off, VF is only written
+    /// on a collision and otherwise keeps its previous value.
+    pub draw_vf_reset: bool,
+    /// Which end of each sprite byte is drawn as the leftmost pixel.
+    pub sprite_bit_order: BitOrder,
+    /// 8xy1/8xy2/8xy3 reset VF to 0 after the operation (COSMAC VIP).
+    pub logic_quirk: bool,
+}
+
+/// The order sprite bits are laid out left to right.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitOrder {
+    /// The most significant bit is the leftmost pixel, as on every standard interpreter
+    #[default]
+    MsbFirst,
+    /// The least significant bit is the leftmost pixel, as some homebrew tools produce
+    LsbFirst,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            display_wait: false,
+            clamp_addresses: false,
+            memory_increments_index: false,
+            memory_increment_by_x: false,
+            jump_uses_vx: false,
+            index_overflow_sets_vf: false,
+            sprite_wrapping: false,
+            sprite_clip_counts_collisions: false,
+            hires_collision_count: false,
+            extended_memory: false,
+            draw_vf_reset: true,
+            sprite_bit_order: BitOrder::MsbFirst,
+            logic_quirk: false,
+        }
+    }
+}
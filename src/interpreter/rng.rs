@@ -0,0 +1,41 @@
+/// SplitMix64, a small random number generator whose whole state is one `u64`, so that it can
+/// be saved and restored along with the rest of the machine.
+#[derive(Debug, Clone)]
+pub(super) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    /// Without `std` there's no entropy source, so every interpreter starts from the same
+    /// seed until the host calls `ChipState::reseed`.
+    #[cfg(not(feature = "std"))]
+    pub fn from_entropy() -> Self {
+        Self::new(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// The high byte of the next output, which has the best statistical quality.
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
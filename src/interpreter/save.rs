@@ -0,0 +1,228 @@
+use super::{Chip8Error, ChipState, Plane, SplitMix64};
+use alloc::vec;
+use alloc::vec::Vec;
+use chip8_base::Pixel;
+use core::time::Duration;
+
+const MAGIC: &[u8; 4] = b"C8SS";
+const VERSION: u8 = 4;
+
+impl ChipState {
+    /// Captures the machine state in a compact binary format for `load_state`.
+    ///
+    /// Configuration (quirks, decode policy, breakpoints, pause) is not included.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+
+        let memory = &self.memory[..self.memory_size()];
+        bytes.extend_from_slice(&(memory.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(memory);
+        bytes.extend_from_slice(&self.registers);
+        bytes.extend_from_slice(&self.pc.to_be_bytes());
+        bytes.extend_from_slice(&self.index.to_be_bytes());
+        bytes.push(self.pointer);
+        bytes.push(self.stack.len() as u8);
+        for &addr in &self.stack {
+            bytes.extend_from_slice(&addr.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.flags);
+
+        // Pack each plane 8 pixels to a byte
+        for plane in &self.display {
+            for row in plane {
+                for pixels in row.chunks(8) {
+                    let byte = pixels
+                        .iter()
+                        .fold(0, |byte, &pixel| byte << 1 | bool::from(pixel) as u8);
+                    bytes.push(byte);
+                }
+            }
+        }
+        bytes.push(self.hires as u8);
+        bytes.push(self.planes);
+
+        bytes.extend_from_slice(&(self.speed.as_nanos() as u64).to_be_bytes());
+        bytes.extend_from_slice(&(self.ticker.as_nanos() as u64).to_be_bytes());
+        bytes.push(self.delay_timer);
+        bytes.push(self.sound_timer);
+        bytes.push(self.audio_pattern.is_some() as u8);
+        bytes.extend_from_slice(&self.audio_pattern.unwrap_or_default());
+        bytes.push(self.pitch);
+        bytes.extend_from_slice(&self.rng.state().to_be_bytes());
+        bytes.push(self.waiting_for_vblank as u8);
+        push_option(&mut bytes, self.waiting_for_key);
+        push_option(&mut bytes, self.key_held);
+        bytes.push(self.halted as u8);
+
+        bytes
+    }
+
+    /// Restores a state produced by `save_state`. On error the current state is left untouched.
+    pub fn load_state(&mut self, mut bytes: &[u8]) -> Result<(), Chip8Error> {
+        let r = &mut bytes;
+
+        if read_array::<4>(r)? != *MAGIC {
+            return Err(invalid("Not a save state"));
+        }
+        if read_u8(r)? != VERSION {
+            return Err(invalid("Unsupported save state version"));
+        }
+
+        let memory_len = u32::from_be_bytes(read_array(r)?) as usize;
+        if memory_len != self.memory_size() {
+            return Err(invalid(
+                "Save state memory size does not match the current mode",
+            ));
+        }
+        let memory = read_slice(r, memory_len)?;
+
+        let registers = read_array::<16>(r)?;
+        let pc = read_u16(r)?;
+        let index = read_u16(r)?;
+        let pointer = read_u8(r)?;
+        let depth = read_u8(r)? as usize;
+        if depth != self.stack.len() {
+            return Err(invalid(
+                "Save state stack depth does not match the interpreter",
+            ));
+        }
+        if pointer as usize > depth {
+            return Err(invalid("Save state stack pointer is out of range"));
+        }
+        let mut stack = vec![0; depth];
+        for addr in stack.iter_mut() {
+            *addr = read_u16(r)?;
+        }
+        let flags = read_array::<8>(r)?;
+
+        let mut display: [Plane; 2] = [[[Pixel::default(); 128]; 64]; 2];
+        for plane in display.iter_mut() {
+            for row in plane.iter_mut() {
+                for pixels in row.chunks_mut(8) {
+                    let byte = read_u8(r)?;
+                    for (i, pixel) in pixels.iter_mut().enumerate() {
+                        *pixel = Pixel::try_from(byte >> (7 - i) & 0b1).unwrap_or_default();
+                    }
+                }
+            }
+        }
+        let hires = read_u8(r)? != 0;
+        let planes = read_u8(r)?;
+
+        let speed = Duration::from_nanos(u64::from_be_bytes(read_array(r)?));
+        let ticker = Duration::from_nanos(u64::from_be_bytes(read_array(r)?));
+        let delay_timer = read_u8(r)?;
+        let sound_timer = read_u8(r)?;
+        let has_pattern = read_u8(r)? != 0;
+        let audio_pattern = read_array::<16>(r)?;
+        let pitch = read_u8(r)?;
+        let rng = SplitMix64::new(u64::from_be_bytes(read_array(r)?));
+        let waiting_for_vblank = read_u8(r)? != 0;
+        let waiting_for_key = read_option(r)?;
+        let key_held = read_option(r)?;
+        if waiting_for_key.is_some_and(|x| x > 0xF) || key_held.is_some_and(|key| key > 0xF) {
+            return Err(invalid("Save state key wait is out of range"));
+        }
+        let halted = read_u8(r)? != 0;
+
+        self.memory[..memory_len].copy_from_slice(memory);
+        self.registers = registers;
+        self.pc = pc;
+        self.index = index;
+        self.pointer = pointer;
+        self.stack = stack;
+        self.flags = flags;
+        self.display = display;
+        self.hires = hires;
+        self.planes = planes;
+        self.mark_all_dirty();
+        self.refresh_frame();
+        self.speed = speed;
+        self.ticker = ticker;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.audio_pattern = has_pattern.then_some(audio_pattern);
+        self.pitch = pitch;
+        self.rng = rng;
+        self.waiting_for_vblank = waiting_for_vblank;
+        self.waiting_for_key = waiting_for_key;
+        self.key_held = key_held;
+        self.halted = halted;
+        self.breakpoint_hit = None;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChipState {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.save_state())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChipState {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes: Vec<u8> = serde::Deserialize::deserialize(deserializer)?;
+        // The clock speed is part of the saved state, so the one given here is replaced
+        let mut state = ChipState::new(700);
+        // load_state only restores into a state with the same memory size and stack depth
+        let (memory_len, depth) = saved_layout(&bytes).map_err(serde::de::Error::custom)?;
+        state.set_quirks(super::Quirks {
+            extended_memory: memory_len > state.memory_size(),
+            ..state.quirks()
+        });
+        state.set_stack_depth(depth);
+        state.load_state(&bytes).map_err(serde::de::Error::custom)?;
+        Ok(state)
+    }
+}
+
+/// The memory size and stack depth recorded in a save state.
+#[cfg(feature = "serde")]
+fn saved_layout(mut bytes: &[u8]) -> Result<(usize, u8), Chip8Error> {
+    let r = &mut bytes;
+    read_array::<5>(r)?;
+    let memory_len = u32::from_be_bytes(read_array(r)?) as usize;
+    // Skip over the memory, registers, pc, I and stack pointer
+    read_slice(r, memory_len + 16 + 2 + 2 + 1)?;
+    Ok((memory_len, read_u8(r)?))
+}
+
+/// Writes a flag byte, then the value or 0.
+fn push_option(bytes: &mut Vec<u8>, value: Option<u8>) {
+    bytes.push(value.is_some() as u8);
+    bytes.push(value.unwrap_or(0));
+}
+
+fn invalid(reason: &'static str) -> Chip8Error {
+    Chip8Error::InvalidSaveState(reason)
+}
+
+/// Takes the next `len` bytes off the front of `r`.
+fn read_slice<'a>(r: &mut &'a [u8], len: usize) -> Result<&'a [u8], Chip8Error> {
+    let (bytes, rest) = r
+        .split_at_checked(len)
+        .ok_or(invalid("Save state is truncated"))?;
+    *r = rest;
+    Ok(bytes)
+}
+
+fn read_array<const N: usize>(r: &mut &[u8]) -> Result<[u8; N], Chip8Error> {
+    Ok(read_slice(r, N)?.try_into().unwrap())
+}
+
+fn read_u8(r: &mut &[u8]) -> Result<u8, Chip8Error> {
+    Ok(read_array::<1>(r)?[0])
+}
+
+fn read_option(r: &mut &[u8]) -> Result<Option<u8>, Chip8Error> {
+    let [flag, value] = read_array(r)?;
+    Ok((flag != 0).then_some(value))
+}
+
+fn read_u16(r: &mut &[u8]) -> Result<u16, Chip8Error> {
+    Ok(u16::from_be_bytes(read_array(r)?))
+}
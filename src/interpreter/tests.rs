@@ -0,0 +1,2540 @@
+use super::*;
+
+const NO_KEYS: Keys = [false; 16];
+
+fn chip(rom: &[u8]) -> ChipState {
+    chip_with(Quirks::default(), rom)
+}
+
+fn chip_with(quirks: Quirks, rom: &[u8]) -> ChipState {
+    let mut state = ChipState::with_quirks(700, quirks);
+    state.load_bytes(rom, 0x200).unwrap();
+    state
+}
+
+fn run(state: &mut ChipState, steps: usize) {
+    for _ in 0..steps {
+        state.step(&NO_KEYS);
+    }
+}
+
+fn run_with(state: &mut ChipState, steps: usize, keys: &Keys) {
+    for _ in 0..steps {
+        state.step(keys);
+    }
+}
+
+fn lit(state: &ChipState, plane: usize, x: usize, y: usize) -> bool {
+    state.display[plane][y][x].into()
+}
+
+#[test]
+fn skips_step_over_long_load() {
+    let mut keys = NO_KEYS;
+    keys[1] = true;
+    // With V0 = 0, V1 = 1 and V2 = 0 each of these skips is taken
+    for skip in [0x3000, 0x4001, 0x5020, 0x9010, 0xE19E, 0xE0A1_u16] {
+        let [high, low] = skip.to_be_bytes();
+        let mut state = chip(&[high, low, 0xF0, 0x00, 0x12, 0x34, 0x63, 0x01]);
+        state.registers[1] = 1;
+        state.step(&keys);
+        assert_eq!(state.pc, 0x206, "{:04X}", skip);
+        state.step(&keys);
+        assert_eq!(state.registers[3], 1, "{:04X}", skip);
+    }
+
+    // A skip that isn't taken still lands on the F000
+    let mut state = chip(&[0x30, 0x01, 0xF0, 0x00, 0x12, 0x34]);
+    run(&mut state, 1);
+    assert_eq!(state.pc, 0x202);
+}
+
+#[test]
+fn sprite_reads_past_end_of_memory_wrap_or_clamp() {
+    // Draw 4 rows from I = 0xFFE, which runs past the end of the 4K memory
+    let rom = [0xAF, 0xFE, 0xD0, 0x04];
+
+    let mut state = chip(&rom);
+    state.memory[0xFFE..0x1000].copy_from_slice(&[0x80, 0x40]);
+    state.memory[0x000..0x002].copy_from_slice(&[0x20, 0x10]);
+    state.memory[0x1000] = 0xFF;
+    run(&mut state, 2);
+    for (y, x) in [(0, 0), (1, 1), (2, 2), (3, 3)] {
+        assert!(lit(&state, 0, x, y));
+    }
+    // The byte above 4K is never read
+    assert!(!lit(&state, 0, 7, 2));
+
+    let quirks = Quirks {
+        clamp_addresses: true,
+        ..Quirks::default()
+    };
+    let mut state = chip_with(quirks, &rom);
+    state.memory[0xFFE..0x1000].copy_from_slice(&[0x80, 0x40]);
+    state.memory[0x000..0x002].copy_from_slice(&[0x20, 0x10]);
+    run(&mut state, 2);
+    for y in 1..4 {
+        assert!(lit(&state, 0, 1, y));
+        assert!(!lit(&state, 0, 2, y) && !lit(&state, 0, 3, y));
+    }
+}
+
+#[test]
+fn save_state_keeps_pending_key_wait() {
+    let rom = [0xF3, 0x0A, 0x12, 0x02];
+    let mut keys = NO_KEYS;
+    let mut state = chip(&rom);
+    let idle = state.save_state();
+    state.step(&keys);
+    keys[5] = true;
+    state.step(&keys);
+    let waiting = state.save_state();
+
+    // Releasing the key after a restore completes the Fx0A that was in progress
+    let mut restored = chip(&rom);
+    restored.load_state(&waiting).unwrap();
+    assert_eq!(restored.waiting_for_key, Some(3));
+    assert_eq!(restored.key_held, Some(5));
+    restored.step(&NO_KEYS);
+    assert_eq!(restored.registers[3], 5);
+    assert_eq!(restored.waiting_for_key, None);
+
+    // Restoring a state saved before the Fx0A drops the wait
+    state.load_state(&idle).unwrap();
+    assert_eq!(state.waiting_for_key, None);
+    assert_eq!(state.key_held, None);
+}
+
+#[test]
+fn save_state_round_trips_flags_audio_and_rng() {
+    // Store V0-V1 in the flags, load a pattern and pitch, then draw random numbers
+    let rom = [
+        0x60, 0x11, 0x61, 0x22, 0xF1, 0x75, 0xA3, 0x00, 0xF0, 0x02, 0xF0, 0x3A, 0xC2, 0xFF, 0xC3,
+        0xFF, 0x12, 0x0C,
+    ];
+    let mut state = chip(&rom);
+    state.reseed(42);
+    state.memory[0x300..0x310].copy_from_slice(&[0xA5; 16]);
+    run(&mut state, 6);
+    let saved = state.save_state();
+    run(&mut state, 2);
+    let rolled = (state.registers[2], state.registers[3]);
+
+    let mut restored = chip(&rom);
+    restored.load_state(&saved).unwrap();
+    assert_eq!(restored.flags()[..2], [0x11, 0x22]);
+    assert_eq!(restored.audio_pattern(), Some(&[0xA5; 16]));
+    assert_eq!(restored.pitch, 0x11);
+    assert_eq!(restored.save_state(), saved);
+    run(&mut restored, 2);
+    assert_eq!((restored.registers[2], restored.registers[3]), rolled);
+
+    // A missing pattern is restored as missing, not as silence
+    let mut fresh = chip(&rom);
+    let blank = fresh.save_state();
+    fresh.load_state(&saved).unwrap();
+    fresh.load_state(&blank).unwrap();
+    assert_eq!(fresh.audio_pattern(), None);
+}
+
+#[test]
+fn protected_writes_check_wrapped_addresses() {
+    // I = 0xFFE, then F555, F533 and 5052 all run past 0xFFF into the fonts
+    for op in [[0xF5, 0x55], [0xF5, 0x33], [0x50, 0x52]] {
+        let mut state = chip(&[0xAF, 0xFE, op[0], op[1]]);
+        state.set_protect_reserved(true);
+        state.registers = [0xEE; 16];
+        let font = state.memory[..0x10].to_vec();
+        run(&mut state, 2);
+        assert_eq!(state.memory[..0x10], font, "{:02X}{:02X}", op[0], op[1]);
+        assert_eq!(state.memory[0xFFE..0x1000], [0, 0]);
+    }
+}
+
+#[test]
+fn code_write_check_follows_wrapped_addresses() {
+    // Loaded at 0x000, so a write wrapping past 0xFFF lands on code that has run
+    let rom = [0xAF, 0xFE, 0xF3, 0x55, 0x10, 0x04];
+    for policy in [CodeWritePolicy::Warn, CodeWritePolicy::Error] {
+        let mut state = ChipState::new(700);
+        state.load_bytes(&rom, 0x000).unwrap();
+        state.set_code_write_policy(policy);
+        state.step(&NO_KEYS);
+        let result = state.try_step(&NO_KEYS);
+        if policy == CodeWritePolicy::Warn {
+            assert!(result.is_ok());
+            assert_eq!(state.last_code_write(), Some(0x000));
+        } else {
+            assert!(matches!(
+                result,
+                Err(Chip8Error::CodeWrite {
+                    pc: 0x002,
+                    addr: 0x000
+                })
+            ));
+            assert_eq!(state.memory[..2], [0xAF, 0xFE]);
+        }
+    }
+}
+
+fn on() -> Pixel {
+    Pixel::try_from(1).unwrap_or_default()
+}
+
+#[test]
+fn overlay_does_not_collide() {
+    // Draw the top row of font 0 (0xF0) at (0, 0)
+    let mut state = chip(&[0xA0, 0x50, 0xD0, 0x01]);
+    assert!(state.set_pixel(0, 0, on()));
+    assert!(state.set_pixel(10, 0, on()));
+    run(&mut state, 2);
+
+    assert_eq!(state.registers[0xF], 0);
+    assert!((0..4).all(|x| lit(&state, 0, x, 0)));
+    assert!(!lit(&state, 0, 10, 0));
+    assert!(bool::from(state.display()[0][10]));
+    assert!(state.get_pixel(10, 0).is_some_and(bool::from));
+
+    state.clear_overlay();
+    assert!(!bool::from(state.display()[0][10]));
+    assert!(bool::from(state.display()[0][0]));
+}
+
+#[test]
+fn get_pixel_reads_every_plane() {
+    let mut state = chip(&[0x00, 0xFF]);
+    state.display[1][3][5] = on();
+    assert!(state.get_pixel(5, 3).is_some_and(bool::from));
+    assert!(state
+        .get_pixel(4, 3)
+        .is_some_and(|pixel| !bool::from(pixel)));
+    assert!(state.render_ascii().lines().nth(3).unwrap().chars().nth(5) != Some(' '));
+}
+
+#[test]
+fn pixel_access_is_bounded_by_resolution() {
+    let mut state = chip(&[0x00, 0xFF]);
+    assert!(state.get_pixel(63, 31).is_some());
+    assert!(state.get_pixel(64, 0).is_none());
+    assert!(state.get_pixel(0, 32).is_none());
+    assert!(state.set_pixel(63, 31, on()));
+    assert!(!state.set_pixel(64, 0, on()));
+    assert!(!state.set_pixel(0, 32, on()));
+
+    run(&mut state, 1);
+    assert!(state.get_pixel(127, 63).is_some());
+    assert!(state.get_pixel(128, 0).is_none());
+    assert!(state.get_pixel(0, 64).is_none());
+    assert!(state.set_pixel(127, 63, on()));
+    assert!(!state.set_pixel(128, 0, on()));
+    assert!(!state.set_pixel(0, 64, on()));
+}
+
+#[test]
+fn timers_drain_at_custom_frequency() {
+    // DT = 120, then spin
+    let rom = [0x60, 0x78, 0xF0, 0x15, 0x12, 0x04];
+    let mut state = chip(&rom);
+    state.set_timer_frequency(120);
+    run(&mut state, 2 + 350);
+    assert!(state.delay_timer().abs_diff(60) <= 1);
+    run(&mut state, 350);
+    assert!(state.delay_timer() <= 1);
+
+    // A rate too high to represent in nanoseconds still ticks instead of dividing by zero
+    let mut state = chip(&rom);
+    state.set_timer_frequency(u32::MAX);
+    run(&mut state, 3);
+    assert_eq!(state.delay_timer(), 0);
+}
+
+#[test]
+fn deeper_stack_holds_more_calls() {
+    // 20 nested calls, each to the next word
+    let rom: Vec<u8> = (0..20)
+        .flat_map(|i: u16| (0x2000 | (0x202 + 2 * i)).to_be_bytes())
+        .collect();
+
+    let mut state = chip_with(Platform::SuperChip.quirks(), &rom);
+    assert!(state.set_stack_depth(32));
+    run(&mut state, 20);
+    assert!(!state.is_halted());
+    assert_eq!(state.stack().len(), 20);
+    assert_eq!(state.pc, 0x228);
+    // Shrinking below the calls in progress is refused
+    assert!(!state.set_stack_depth(16));
+
+    let mut state = chip_with(Platform::SuperChip.quirks(), &rom);
+    for _ in 0..16 {
+        assert!(state.try_step(&NO_KEYS).is_ok());
+    }
+    assert!(matches!(
+        state.try_step(&NO_KEYS),
+        Err(Chip8Error::StackOverflow { .. })
+    ));
+}
+
+#[test]
+fn quirks_can_be_changed_after_construction() {
+    let mut state = chip(&[0x00, 0xFF]);
+    state.set_quirks(Quirks {
+        extended_memory: true,
+        ..Quirks::default()
+    });
+    assert!(state.set_index(0xFFFF));
+    assert_eq!(state.memory().len(), 0x10000);
+
+    state.set_quirks(Quirks::default());
+    assert_eq!(state.index, 0x0FFF);
+    assert_eq!(state.memory().len(), 0x1000);
+}
+
+#[test]
+fn custom_font_moves_fx29() {
+    let font: [u8; 80] = std::array::from_fn(|i| i as u8);
+    let mut state = chip(&[0x60, 0x03, 0xF0, 0x29, 0xD1, 0x11]);
+    assert!(matches!(
+        state.set_font(&font, 0x1C0),
+        Err(Chip8Error::FontOutOfRange { offset: 0x1C0 })
+    ));
+    state.set_font(&font, 0x100).unwrap();
+    assert_eq!(state.memory[0x50..0xA0], [0; 80]);
+
+    run(&mut state, 3);
+    // Digit 3 starts 15 bytes in, and its first byte (15 = 0b1111) is what gets drawn
+    assert_eq!(state.index, 0x10F);
+    assert!((4..8).all(|x| lit(&state, 0, x, 0)));
+    assert!((0..4).all(|x| !lit(&state, 0, x, 0)));
+}
+
+#[test]
+fn memory_init_pattern_spares_fonts_and_rom() {
+    let mut state = ChipState::with_quirks(700, Platform::SuperChip.quirks());
+    state.load_bytes(&[0x60, 0x01], 0x200).unwrap();
+    state.set_memory_init(MemoryInit::Pattern(0xCD));
+
+    assert_eq!(state.registers, [0xCD; 16]);
+    assert_eq!(state.memory[..0x50], [0xCD; 0x50]);
+    assert_eq!(state.memory[0x50..0xA0], font::FONT);
+    assert_eq!(state.memory[0xA0..0x104], font::BIG_FONT);
+    assert!(state.memory[0x104..0x200].iter().all(|&byte| byte == 0xCD));
+    assert_eq!(state.memory[0x200..0x202], [0x60, 0x01]);
+    assert!(state.memory[0x202..].iter().all(|&byte| byte == 0xCD));
+    // The quirks are left as they were
+    assert!(state.quirks().jump_uses_vx);
+}
+
+#[test]
+fn superchip_counts_collided_rows_in_hires_only() {
+    // Draw font 0 (5 rows) twice at (0, 0), in lores then in hires
+    let draw_twice = [0xA0, 0x50, 0xD0, 0x05, 0xD0, 0x05];
+    let mut state = chip_with(Platform::SuperChip.quirks(), &draw_twice);
+    run(&mut state, 3);
+    assert_eq!(state.registers[0xF], 1);
+
+    let mut rom = vec![0x00, 0xFF];
+    rom.extend_from_slice(&draw_twice);
+    let mut state = chip_with(Platform::SuperChip.quirks(), &rom);
+    run(&mut state, 4);
+    assert_eq!(state.registers[0xF], 5);
+}
+
+#[test]
+fn detect_platform_from_signature_opcodes() {
+    let plain = [0x60, 0x01, 0xA0, 0x50, 0xD0, 0x15, 0x12, 0x00];
+    assert_eq!(detect_platform(&plain), Platform::Chip8);
+    assert_eq!(detect_platform(&[]), Platform::Chip8);
+    for schip in [
+        0x00FF, 0x00FE, 0x00C4, 0x00FB, 0x00FC, 0xF130, 0xF175, 0xF185_u16,
+    ] {
+        let rom = [&plain[..], &schip.to_be_bytes()].concat();
+        assert_eq!(detect_platform(&rom), Platform::SuperChip, "{:04X}", schip);
+    }
+    for xochip in [0xF000, 0xF201, 0xF002, 0x5122, 0x5123_u16] {
+        // Extensions win over SUPER-CHIP opcodes found earlier
+        let rom = [&[0x00, 0xFF][..], &xochip.to_be_bytes()].concat();
+        assert_eq!(detect_platform(&rom), Platform::XoChip, "{:04X}", xochip);
+    }
+
+    assert!(!Platform::Chip8.quirks().shift_uses_vy);
+    assert!(Platform::Vip.quirks().shift_uses_vy);
+    assert!(Platform::Vip.quirks().display_wait);
+}
+
+#[test]
+fn step_frame_ticks_timers_once_per_frame() {
+    // DT = 60, then spin
+    let rom = [0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04];
+    for ipf in [2, 10, 1000] {
+        let mut state = chip(&rom);
+        state.step_frame(ipf, &NO_KEYS);
+        assert_eq!(state.delay_timer(), 59, "ipf {}", ipf);
+        for frame in 1..=5 {
+            state.step_frame(ipf, &NO_KEYS);
+            assert_eq!(state.delay_timer(), 59 - frame, "ipf {}", ipf);
+        }
+    }
+}
+
+#[test]
+fn step_frame_stops_at_breakpoints_and_watchpoints() {
+    let rom = [0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x12, 0x06];
+    let mut state = chip(&rom);
+    state.add_breakpoint(0x204);
+    state.step_frame(10, &NO_KEYS);
+    assert_eq!(state.pc, 0x204);
+    assert_eq!(state.registers[2], 0);
+    state.step_frame(1, &NO_KEYS);
+    assert_eq!(state.registers[2], 3);
+
+    let mut state = chip(&rom);
+    state.watch_register(1);
+    state.step_frame(10, &NO_KEYS);
+    assert_eq!(state.pc, 0x204);
+    assert_eq!(state.registers[1], 2);
+}
+
+#[test]
+fn render_ascii_shows_font_glyph() {
+    // Draw the digit 0 at (1, 2)
+    let mut state = chip(&[0x60, 0x01, 0x61, 0x02, 0xA0, 0x50, 0xD0, 0x15]);
+    run(&mut state, 4);
+    let text = state.render_ascii();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 32);
+    assert!(lines.iter().all(|line| line.len() == 64));
+    let glyph: Vec<&str> = lines[2..7].iter().map(|line| &line[..6]).collect();
+    assert_eq!(glyph, [" #### ", " #  # ", " #  # ", " #  # ", " #### "]);
+    assert_eq!(text, crate::render::to_ascii(state.display()));
+
+    state.hires = true;
+    let text = state.render_ascii();
+    assert_eq!(text.lines().count(), 64);
+    assert!(text.lines().all(|line| line.len() == 128));
+}
+
+#[test]
+fn load_rejects_empty_and_oversized_roms() {
+    let mut state = ChipState::new(700);
+    assert!(matches!(
+        state.load_bytes(&[], 0x200),
+        Err(Chip8Error::EmptyRom)
+    ));
+    assert!(matches!(
+        state.load_bytes(&[0x12; 4000], 0x200),
+        Err(Chip8Error::RomTooLarge {
+            size: 4000,
+            capacity: 0xE00
+        })
+    ));
+    // Nothing was loaded by either
+    assert_eq!(state.rom_info(), None);
+    assert_eq!(state.memory[0x200], 0);
+
+    state.load_bytes(&[0x12; 0xE00], 0x200).unwrap();
+    assert_eq!(state.rom_info().map(|info| info.end), Some(0xFFF));
+}
+
+#[test]
+fn phosphor_decay_fades_per_tick() {
+    // Draw the top row of font 0, then erase it and spin
+    let rom = [0xA0, 0x50, 0xD0, 0x01, 0xD0, 0x01, 0x12, 0x06];
+    let mut state = chip(&rom);
+    state.set_phosphor_decay(100);
+    state.step_frame(2, &NO_KEYS);
+    assert_eq!(state.render_intensity()[0][..5], [255, 255, 255, 255, 0]);
+    state.step_frame(1, &NO_KEYS);
+    assert_eq!(state.render_intensity()[0][..5], [155, 155, 155, 155, 0]);
+    state.step_frame(1, &NO_KEYS);
+    assert_eq!(state.render_intensity()[0][..5], [55, 55, 55, 55, 0]);
+    state.step_frame(1, &NO_KEYS);
+    assert_eq!(state.render_intensity()[0][0], 0);
+
+    // The default turns pixels off at once, even when stepping without building frames
+    let mut state = chip(&rom);
+    for _ in 0..2 {
+        state.step_compute(&NO_KEYS);
+    }
+    state.apply_ticks(1);
+    assert_eq!(state.render_intensity()[0][..5], [255, 255, 255, 255, 0]);
+    state.step_compute(&NO_KEYS);
+    state.apply_ticks(1);
+    assert_eq!(state.render_intensity()[0][..5], [0; 5]);
+}
+
+#[test]
+fn display_hash_matches_golden_values() {
+    let mut state = chip(&[0xA0, 0x50, 0xD0, 0x05]);
+    assert_eq!(state.display_hash(), 0xB93A_0C83_CE3B_6325);
+    run(&mut state, 2);
+    assert_eq!(state.display_hash(), 0x2DB3_FF37_3CD8_DBF3);
+
+    // A hires display hashes its full 128x64 area
+    let mut hires = chip(&[0x00, 0xFF, 0xA0, 0x50, 0xD0, 0x05]);
+    run(&mut hires, 3);
+    assert_eq!(hires.display_hash(), 0x5108_7DC0_7B88_7FF3);
+    // Pixels that downscale into the same frame still hash differently
+    hires.display[0][1][1] = on();
+    assert_ne!(hires.display_hash(), 0x5108_7DC0_7B88_7FF3);
+}
+
+#[test]
+fn key_history_keeps_running_steps_in_order() {
+    let states: Vec<Keys> = (0..6)
+        .map(|i| std::array::from_fn(|key| key == i))
+        .collect();
+    let mut state = chip(&[0x12, 0x00]);
+    state.set_key_history(4);
+    state.step(&states[0]);
+    state.step(&states[1]);
+    state.pause();
+    state.step(&states[2]);
+    state.resume();
+    for keys in &states[3..] {
+        state.step(keys);
+    }
+    // The oldest step was dropped, and the paused one never recorded
+    let expected = [states[1], states[3], states[4], states[5]];
+    assert_eq!(state.key_history(), &expected[..]);
+
+    state.set_key_history(2);
+    assert_eq!(state.key_history(), &expected[2..]);
+
+    state.halted = true;
+    state.step(&states[0]);
+    assert_eq!(state.key_history(), &expected[2..]);
+}
+
+#[test]
+fn step_frame_reports_buzzer_edges_after_the_tick() {
+    // ST = 2, then spin
+    let mut state = chip(&[0x60, 0x02, 0xF0, 0x18, 0x12, 0x04]);
+    let mut edges = Vec::new();
+    for _ in 0..4 {
+        state.step_frame(3, &NO_KEYS);
+        edges.push(state.buzzer_edge());
+    }
+    assert_eq!(edges, [Some(true), Some(false), None, None]);
+
+    // A buzz that starts and ends within one frame has no net edge
+    let mut state = chip(&[0x60, 0x01, 0xF0, 0x18, 0x12, 0x04]);
+    state.step_frame(3, &NO_KEYS);
+    assert_eq!(state.buzzer_edge(), None);
+    assert!(!state.buzzer_active());
+}
+
+#[test]
+fn step_limited_coalesces_draws_into_one_frame() {
+    // Draw font 0, 1 and 2 side by side, then spin
+    let rom = [
+        0xA0, 0x50, 0xD0, 0x15, 0x60, 0x08, 0xA0, 0x55, 0xD0, 0x15, 0x60, 0x10, 0xA0, 0x5A, 0xD0,
+        0x15, 0x12, 0x10,
+    ];
+    let mut state = chip(&rom);
+    // 700Hz gives 11 or 12 steps per 60Hz tick
+    let frames: Vec<(usize, Display)> = (0..12)
+        .filter_map(|i| Some((i, state.step_limited(&NO_KEYS)?)))
+        .collect();
+    assert_eq!(frames.len(), 1);
+    assert!(frames[0].0 >= 8);
+    let frame = frames[0].1;
+    assert!(bool::from(frame[0][0]) && bool::from(frame[0][10]) && bool::from(frame[0][16]));
+
+    // A draw made just before pausing isn't held back until the next tick
+    let mut state = chip(&rom);
+    assert!(state.step_limited(&NO_KEYS).is_none());
+    assert!(state.step_limited(&NO_KEYS).is_none());
+    state.pause();
+    let frame = state.step_limited(&NO_KEYS).unwrap();
+    assert!(bool::from(frame[0][0]));
+    assert!(state.step_limited(&NO_KEYS).is_none());
+}
+
+#[test]
+fn pitch_sets_playback_frequency() {
+    for (pitch, hz) in [(64, 4000.0), (112, 8000.0), (16, 2000.0), (0, 1587.4)] {
+        let mut state = chip(&[0x60, pitch, 0xF0, 0x3A]);
+        run(&mut state, 2);
+        assert!(
+            (state.playback_frequency() - hz).abs() < 0.1,
+            "pitch {}",
+            pitch
+        );
+    }
+}
+
+#[test]
+fn fetch_at_end_of_memory_wraps_or_clamps() {
+    for clamp in [false, true] {
+        let quirks = Quirks {
+            clamp_addresses: clamp,
+            ..Quirks::default()
+        };
+        let mut state = chip_with(quirks, &[0x00, 0xE0]);
+        state.memory[0xFFE..0x1000].copy_from_slice(&[0x60, 0x61]);
+        state.memory[0x000] = 0x23;
+
+        assert!(state.set_pc(0xFFE));
+        run(&mut state, 1);
+        assert_eq!(state.registers[0], 0x61);
+        assert_eq!(state.pc, 0x000);
+
+        // The second byte of an opcode at 0xFFF comes from 0x000, or 0xFFF again if clamped
+        assert!(state.set_pc(0xFFF));
+        run(&mut state, 1);
+        assert_eq!(state.registers[1], if clamp { 0x61 } else { 0x23 });
+        assert_eq!(state.pc, 0x001);
+    }
+}
+
+/// A straightforward Dxyn for lores plane 0, to check the optimised one against.
+fn reference_draw(
+    display: &mut [[bool; 64]; 32],
+    sprite: &[u8],
+    (x, y): (u8, u8),
+    quirks: &Quirks,
+) -> u8 {
+    let mut vf = 0;
+    for (row, byte) in sprite.iter().enumerate() {
+        for col in 0..8 {
+            let bit = match quirks.sprite_bit_order {
+                BitOrder::MsbFirst => byte >> (7 - col) & 1,
+                BitOrder::LsbFirst => byte >> col & 1,
+            };
+            let (mut px, mut py) = (x as usize % 64 + col, y as usize % 32 + row);
+            if quirks.sprite_wrapping {
+                px %= 64;
+                py %= 32;
+            } else if px >= 64 || py >= 32 {
+                continue;
+            }
+            if bit == 1 {
+                vf |= display[py][px] as u8;
+                display[py][px] = !display[py][px];
+            }
+        }
+    }
+    vf
+}
+
+#[test]
+fn draw_matches_reference_implementation() {
+    let mut rng = SplitMix64::new(72);
+    for case in 0..400 {
+        let quirks = Quirks {
+            sprite_wrapping: case % 2 == 1,
+            sprite_bit_order: if case % 4 >= 2 {
+                BitOrder::LsbFirst
+            } else {
+                BitOrder::MsbFirst
+            },
+            ..Quirks::default()
+        };
+        let n = rng.next_u8() % 16;
+        let mut state = chip_with(quirks, &[0xA3, 0x00, 0xD0, 0x10 | n]);
+        state.registers[0] = rng.next_u8();
+        state.registers[1] = rng.next_u8();
+        for byte in &mut state.memory[0x300..0x30F] {
+            *byte = rng.next_u8();
+        }
+
+        let mut expected = [[false; 64]; 32];
+        for (y, row) in expected.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = rng.next_u8() < 64;
+                state.display[0][y][x] = Pixel::try_from(*pixel as u8).unwrap_or_default();
+            }
+        }
+        let sprite = state.memory[0x300..0x300 + n as usize].to_vec();
+        let vf = reference_draw(
+            &mut expected,
+            &sprite,
+            (state.registers[0], state.registers[1]),
+            &quirks,
+        );
+
+        run(&mut state, 2);
+        assert_eq!(state.registers[0xF], vf, "case {}", case);
+        for (y, row) in expected.iter().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                assert_eq!(
+                    lit(&state, 0, x, y),
+                    pixel,
+                    "case {} at ({}, {})",
+                    case,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn shr_uses_vy_only_under_quirk() {
+    // V1 = 0x05, V2 = 0x82, then 8126
+    let rom = [0x61, 0x05, 0x62, 0x82, 0x81, 0x26];
+    let mut state = chip(&rom);
+    run(&mut state, 3);
+    assert_eq!((state.registers[1], state.registers[0xF]), (0x02, 1));
+    assert_eq!(state.registers[2], 0x82);
+
+    let quirks = Quirks {
+        shift_uses_vy: true,
+        ..Quirks::default()
+    };
+    let mut state = chip_with(quirks, &rom);
+    run(&mut state, 3);
+    assert_eq!((state.registers[1], state.registers[0xF]), (0x41, 0));
+    assert_eq!(state.registers[2], 0x82);
+}
+
+#[test]
+fn flag_wins_when_vf_is_the_destination() {
+    // (instruction, VF before, flag expected in VF rather than the result)
+    for (instruction, vf, flag) in [
+        (Instruction::Add(0xF, 1), 0xF0, 1),
+        (Instruction::Sub(0xF, 1), 0x10, 0),
+        (Instruction::Ssub(0xF, 1), 0x10, 1),
+        (Instruction::Shr(0xF, 1), 0x05, 1),
+        (Instruction::Shl(0xF, 1), 0x41, 0),
+    ] {
+        let mut state = chip(&[0x00, 0xE0]);
+        state.registers[1] = 0x20;
+        state.registers[0xF] = vf;
+        state.execute(instruction, &NO_KEYS).unwrap();
+        assert_eq!(state.registers[0xF], flag, "{:?}", instruction);
+    }
+}
+
+#[test]
+fn display_wait_draws_once_per_timer_period() {
+    // Draw font 0 over and over
+    let rom = [0xA0, 0x50, 0xD0, 0x15, 0x12, 0x02];
+    let draws_per_period = |quirks: Quirks| {
+        let mut state = chip_with(quirks, &rom);
+        let (mut draws, mut most) = (0, 0);
+        for _ in 0..700 {
+            let drew = state.step(&NO_KEYS).is_some();
+            // The timers tick before the instruction runs, so a draw on that step is the
+            // first of the new period
+            if state.ticked {
+                draws = 0;
+            }
+            draws += drew as u32;
+            most = most.max(draws);
+        }
+        most
+    };
+
+    let quirks = Quirks {
+        display_wait: true,
+        ..Quirks::default()
+    };
+    assert_eq!(draws_per_period(quirks), 1);
+    assert!(draws_per_period(Quirks::default()) > 1);
+}
+
+#[test]
+fn stack_overflow_and_underflow_halt_cleanly() {
+    // Calls itself forever
+    let mut state = chip(&[0x22, 0x00]);
+    for depth in 1..=16 {
+        assert!(state.try_step(&NO_KEYS).is_ok());
+        assert_eq!(state.stack().len(), depth);
+    }
+    assert!(matches!(
+        state.try_step(&NO_KEYS),
+        Err(Chip8Error::StackOverflow { pc: 0x202 })
+    ));
+    assert!(state.is_halted());
+    assert_eq!(state.stack().len(), 16);
+
+    let mut state = chip(&[0x00, 0xEE]);
+    assert!(matches!(
+        state.try_step(&NO_KEYS),
+        Err(Chip8Error::StackUnderflow { pc: 0x202 })
+    ));
+    assert!(state.is_halted());
+    assert_eq!(state.pointer, 0);
+}
+
+#[test]
+fn illegal_opcodes_are_errors() {
+    for opcode in [0x5AB1, 0x8AB8, 0xE123_u16] {
+        assert_eq!(
+            Instruction::decode(opcode, 0x200),
+            Err(DecodeError { opcode, pc: 0x200 })
+        );
+
+        let mut state = chip(&opcode.to_be_bytes());
+        assert!(matches!(
+            state.try_step(&NO_KEYS),
+            Err(Chip8Error::IllegalOpcode(DecodeError { pc: 0x200, .. }))
+        ));
+        assert!(state.is_halted());
+
+        let mut state = chip(&opcode.to_be_bytes());
+        state.set_decode_policy(DecodePolicy::Nop);
+        assert!(state.try_step(&NO_KEYS).is_ok());
+        assert!(!state.is_halted());
+        assert_eq!(state.pc, 0x202);
+    }
+}
+
+#[test]
+fn store_and_load_advance_index_per_quirk() {
+    for (increments, by_x, index) in [
+        (false, false, 0x300),
+        (true, false, 0x304),
+        (true, true, 0x303),
+    ] {
+        let quirks = Quirks {
+            memory_increments_index: increments,
+            memory_increment_by_x: by_x,
+            ..Quirks::default()
+        };
+        for op in [0x55, 0x65] {
+            let mut state = chip_with(quirks, &[0xA3, 0x00, 0xF3, op]);
+            run(&mut state, 2);
+            assert_eq!(state.index, index, "{:?} F3{:02X}", quirks, op);
+        }
+    }
+}
+
+#[test]
+fn jump_with_offset_per_quirk() {
+    // V0 = 0x10, V3 = 0x20, then B300
+    let rom = [0x60, 0x10, 0x63, 0x20, 0xB3, 0x00];
+    let mut state = chip(&rom);
+    run(&mut state, 3);
+    assert_eq!(state.pc, 0x310);
+
+    let quirks = Quirks {
+        jump_uses_vx: true,
+        ..Quirks::default()
+    };
+    let mut state = chip_with(quirks, &rom);
+    run(&mut state, 3);
+    assert_eq!(state.pc, 0x320);
+
+    // The target wraps at 12 bits
+    let mut state = chip(&[0x60, 0x10, 0xBF, 0xFF]);
+    run(&mut state, 2);
+    assert_eq!(state.pc, 0x00F);
+}
+
+#[test]
+fn sprites_wrap_or_clip_at_edges() {
+    // Draw font 0 at (62, 30), so it runs off the right and bottom edges
+    let rom = [0x60, 0x3E, 0x61, 0x1E, 0xA0, 0x50, 0xD0, 0x15];
+    let mut state = chip(&rom);
+    run(&mut state, 4);
+    assert!(lit(&state, 0, 62, 30) && lit(&state, 0, 63, 30));
+    assert!(!lit(&state, 0, 0, 30) && !lit(&state, 0, 1, 30));
+    assert!(state.display[0][..30]
+        .iter()
+        .flatten()
+        .all(|&p| !bool::from(p)));
+
+    let quirks = Quirks {
+        sprite_wrapping: true,
+        ..Quirks::default()
+    };
+    let mut state = chip_with(quirks, &rom);
+    run(&mut state, 4);
+    assert!(lit(&state, 0, 62, 30) && lit(&state, 0, 63, 30));
+    assert!(lit(&state, 0, 0, 30) && lit(&state, 0, 1, 30));
+    // The third row, 0x90, lands on row 0 split across both edges
+    assert!(lit(&state, 0, 62, 0) && lit(&state, 0, 1, 0));
+    assert!(!lit(&state, 0, 63, 0) && !lit(&state, 0, 0, 0));
+}
+
+#[test]
+fn cls_clears_active_resolution() {
+    // Hires, draw font 0 at (100, 50), clear, then back to lores
+    let rom = [
+        0x00, 0xFF, 0x60, 0x64, 0x61, 0x32, 0xA0, 0x50, 0xD0, 0x15, 0x00, 0xE0, 0x00, 0xFE,
+    ];
+    let mut state = chip(&rom);
+    run(&mut state, 5);
+    assert!(lit(&state, 0, 100, 50));
+    assert_eq!(state.render_rgb([255; 3], [0; 3]).len(), 128 * 64 * 4);
+
+    run(&mut state, 1);
+    assert_eq!(
+        state.take_dirty_region().map(|d| (d.width, d.height)),
+        Some((128, 64))
+    );
+    assert!(state.display[0].iter().flatten().all(|&p| !bool::from(p)));
+
+    run(&mut state, 1);
+    assert!(!state.hires);
+    assert_eq!(state.render_rgb([255; 3], [0; 3]).len(), 64 * 32 * 4);
+    assert_eq!(state.render_ascii().lines().count(), 32);
+}
+
+#[test]
+fn scrolls_move_pixels_by_their_offsets() {
+    // Plot one pixel at (10, 5), then scroll down 3, right 4 and left 4
+    let rom = [
+        0x60, 0x0A, 0x61, 0x05, 0xA3, 0x00, 0xD0, 0x11, 0x00, 0xC3, 0x00, 0xFB, 0x00, 0xFC,
+    ];
+    let mut state = chip(&rom);
+    state.memory[0x300] = 0x80;
+    let lit_pixels = |state: &ChipState| -> Vec<(usize, usize)> {
+        (0..32)
+            .flat_map(|y| (0..64).map(move |x| (x, y)))
+            .filter(|&(x, y)| lit(state, 0, x, y))
+            .collect()
+    };
+
+    run(&mut state, 4);
+    assert_eq!(lit_pixels(&state), [(10, 5)]);
+    run(&mut state, 1);
+    assert_eq!(lit_pixels(&state), [(10, 8)]);
+    run(&mut state, 1);
+    assert_eq!(lit_pixels(&state), [(14, 8)]);
+    run(&mut state, 1);
+    assert_eq!(lit_pixels(&state), [(10, 8)]);
+}
+
+#[test]
+fn big_font_index_per_digit() {
+    for digit in 0..10 {
+        let mut state = chip(&[0x60, digit, 0xF0, 0x30]);
+        run(&mut state, 2);
+        assert_eq!(state.index, 0xA0 + 10 * digit as u16);
+        let glyph = &state.memory[state.index as usize..state.index as usize + 10];
+        assert_eq!(glyph, &font::BIG_FONT[10 * digit as usize..][..10]);
+    }
+}
+
+#[test]
+fn big_sprite_draws_16x16() {
+    // Hires, then draw the 32-byte sprite at 0x300 to (20, 10) twice
+    let rom = [
+        0x00, 0xFF, 0x60, 0x14, 0x61, 0x0A, 0xA3, 0x00, 0xD0, 0x10, 0xD0, 0x10,
+    ];
+    let mut state = chip(&rom);
+    for row in 0..16 {
+        state.memory[0x300 + 2 * row] = 0xAA;
+        state.memory[0x301 + 2 * row] = 1 << (row % 8);
+    }
+
+    run(&mut state, 5);
+    assert_eq!(state.registers[0xF], 0);
+    for row in 0..16 {
+        for col in 0..16 {
+            let expected = if col < 8 {
+                col % 2 == 0
+            } else {
+                15 - col == row % 8
+            };
+            assert_eq!(
+                lit(&state, 0, 20 + col, 10 + row),
+                expected,
+                "({}, {})",
+                col,
+                row
+            );
+        }
+    }
+    assert!(!lit(&state, 0, 36, 10) && !lit(&state, 0, 20, 26));
+
+    run(&mut state, 1);
+    assert_eq!(state.registers[0xF], 1);
+    assert!(state.display[0].iter().flatten().all(|&p| !bool::from(p)));
+}
+
+#[test]
+fn planes_draw_and_clear_independently() {
+    // Draw font 0 on plane 1 then plane 0, then clear plane 0 only
+    let rom = [
+        0xA0, 0x50, 0xF2, 0x01, 0xD0, 0x05, 0xF1, 0x01, 0xD0, 0x05, 0x00, 0xE0,
+    ];
+    let mut state = chip_with(Platform::XoChip.quirks(), &rom);
+    run(&mut state, 3);
+    assert!(lit(&state, 1, 0, 0));
+    assert!(!lit(&state, 0, 0, 0));
+
+    run(&mut state, 2);
+    assert_eq!(state.registers[0xF], 0);
+    assert!(lit(&state, 0, 0, 0) && lit(&state, 1, 0, 0));
+
+    run(&mut state, 1);
+    assert!(!lit(&state, 0, 0, 0));
+    assert!(lit(&state, 1, 0, 0));
+}
+
+#[test]
+fn timers_drain_in_a_second_at_any_clock() {
+    // DT = 60, then spin
+    let rom = [0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04];
+    for freq in [60, 500, 700, 1000, 1234] {
+        let mut state = ChipState::new(freq);
+        state.load_bytes(&rom, 0x200).unwrap();
+        run(&mut state, 2 + freq as usize / 2);
+        assert!(state.delay_timer().abs_diff(30) <= 1, "{}Hz", freq);
+        run(&mut state, freq as usize / 2);
+        assert!(state.delay_timer() <= 1, "{}Hz", freq);
+        run(&mut state, 2);
+        assert_eq!(state.delay_timer(), 0, "{}Hz", freq);
+    }
+}
+
+#[test]
+fn pause_freezes_pc_and_timers() {
+    let mut state = chip(&[0x60, 0x3C, 0xF0, 0x15, 0x70, 0x01, 0x12, 0x04]);
+    run(&mut state, 2);
+    state.pause();
+    assert!(state.is_paused());
+    let (pc, v0, dt) = (state.pc(), state.registers()[0], state.delay_timer());
+    run(&mut state, 100);
+    assert_eq!(state.pc(), pc);
+    assert_eq!(state.registers()[0], v0);
+    assert_eq!(state.delay_timer(), dt);
+    state.resume();
+    assert!(!state.is_paused());
+    run(&mut state, 1);
+    assert_eq!(state.pc(), pc + 2);
+    assert_eq!(state.registers()[0], v0 + 1);
+}
+
+#[test]
+fn trace_records_the_executed_instruction() {
+    let mut state = chip(&[0x60, 0x05, 0x71, 0x02]);
+    let (_, trace) = state.step_with_trace(&NO_KEYS);
+    assert_eq!(trace.pc, 0x200);
+    assert_eq!(trace.opcode, 0x6005);
+    assert_eq!(trace.instruction, Some(Instruction::Setr(0, 0x05)));
+
+    state.pause();
+    let (frame, trace) = state.step_with_trace(&NO_KEYS);
+    assert!(frame.is_none());
+    assert_eq!(trace.pc, 0x202);
+    assert_eq!(trace.opcode, 0);
+    assert_eq!(trace.instruction, None);
+
+    state.resume();
+    let (_, trace) = state.step_with_trace(&NO_KEYS);
+    assert_eq!((trace.pc, trace.opcode), (0x202, 0x7102));
+    assert_eq!(trace.instruction, Some(Instruction::Addr(1, 0x02)));
+}
+
+#[test]
+fn accessors_report_state() {
+    let mut state = chip(&[
+        0x60, 0x05, 0x61, 0x0A, 0xA1, 0x23, 0xF0, 0x15, 0xF1, 0x18, 0x22, 0x0C, 0x12, 0x0C,
+    ]);
+    run(&mut state, 6);
+    assert_eq!(state.registers()[..2], [0x05, 0x0A]);
+    assert_eq!(state.index(), 0x123);
+    assert_eq!(state.pc(), 0x20C);
+    assert_eq!(state.stack(), [0x20C]);
+    assert_eq!(state.delay_timer(), 0x05);
+    assert_eq!(state.sound_timer(), 0x0A);
+    assert_eq!(state.memory().len(), 4096);
+    assert_eq!(state.memory()[0x200..0x204], [0x60, 0x05, 0x61, 0x0A]);
+}
+
+#[test]
+fn breakpoint_stops_before_executing() {
+    // V0 = 5, then loop adding 1
+    let mut state = chip(&[0x60, 0x05, 0x70, 0x01, 0x12, 0x02]);
+    state.add_breakpoint(0x202);
+    assert!(matches!(
+        state.step_debug(&NO_KEYS),
+        StepOutcome::Executed(_)
+    ));
+    let cycles = state.cycle_count();
+    assert!(matches!(
+        state.step_debug(&NO_KEYS),
+        StepOutcome::Breakpoint(0x202)
+    ));
+    assert_eq!(state.pc(), 0x202);
+    assert_eq!(state.registers()[0], 5);
+    assert_eq!(state.cycle_count(), cycles);
+
+    // Continuing runs the instruction, and the loop comes back round to the breakpoint
+    run(&mut state, 2);
+    assert_eq!(state.registers()[0], 6);
+    assert!(matches!(
+        state.step_debug(&NO_KEYS),
+        StepOutcome::Breakpoint(0x202)
+    ));
+
+    state.remove_breakpoint(0x202);
+    run(&mut state, 4);
+    assert_eq!(state.registers()[0], 8);
+}
+
+#[test]
+fn save_state_restores_saved_point() {
+    // DT = 60, draw a font glyph, then loop adding to V0 and redrawing
+    let mut state = chip(&[
+        0x60, 0x3C, 0xF0, 0x15, 0xA0, 0x50, 0xD1, 0x15, 0x70, 0x01, 0xD1, 0x15, 0x12, 0x08,
+    ]);
+    run(&mut state, 30);
+    let saved = state.save_state();
+    let (registers, pc, timer) = (*state.registers(), state.pc(), state.delay_timer());
+    let hash = state.display_hash();
+
+    run(&mut state, 25);
+    assert_ne!(*state.registers(), registers);
+    assert_ne!(state.delay_timer(), timer);
+
+    state.load_state(&saved).unwrap();
+    assert_eq!(*state.registers(), registers);
+    assert_eq!(state.pc(), pc);
+    assert_eq!(state.delay_timer(), timer);
+    assert_eq!(state.display_hash(), hash);
+    assert_eq!(state.save_state(), saved);
+    assert!(matches!(
+        state.load_state(&saved[..saved.len() / 2]),
+        Err(Chip8Error::InvalidSaveState(_))
+    ));
+}
+
+#[test]
+fn reset_keeps_rom_and_font() {
+    let rom = [
+        0x60, 0x05, 0xA0, 0x50, 0xF0, 0x15, 0xD0, 0x05, 0x22, 0x0A, 0x12, 0x0A,
+    ];
+    let mut state = chip(&rom);
+    run(&mut state, 6);
+    assert!(lit(&state, 0, 5, 5));
+    assert_eq!(state.stack().len(), 1);
+
+    state.reset();
+    assert_eq!(state.pc(), 0x200);
+    assert_eq!(*state.registers(), [0; 16]);
+    assert_eq!(state.index(), 0);
+    assert!(state.stack().is_empty());
+    assert_eq!((state.delay_timer(), state.sound_timer()), (0, 0));
+    assert!(state
+        .display
+        .iter()
+        .flatten()
+        .flatten()
+        .all(|&p| !bool::from(p)));
+    assert_eq!(state.memory()[0x200..0x20C], rom);
+    assert_eq!(state.memory()[0x50], 0xF0);
+
+    run(&mut state, 4);
+    assert!(lit(&state, 0, 5, 5));
+}
+
+#[test]
+fn key_wait_completes_on_release() {
+    let mut state = chip(&[0xF3, 0x0A, 0x70, 0x01, 0x12, 0x04]);
+    let mut keys = NO_KEYS;
+    state.step(&keys);
+    keys[0xB] = true;
+    for _ in 0..5 {
+        state.step(&keys);
+        assert_eq!(state.pc(), 0x202);
+        assert_eq!(state.registers()[3], 0);
+    }
+    keys[0xB] = false;
+    state.step(&keys);
+    assert_eq!(state.registers()[3], 0xB);
+    state.step(&keys);
+    assert_eq!(state.pc(), 0x204);
+    assert_eq!(state.registers()[0], 1);
+}
+
+#[test]
+fn skp_reads_through_keymap() {
+    let rom = [0x60, 0x01, 0xE0, 0x9E, 0x61, 0x01, 0x62, 0x01];
+    let mut keymap: [usize; 16] = std::array::from_fn(|k| k);
+    keymap[1] = 7;
+
+    let mut remapped = NO_KEYS;
+    remapped[7] = true;
+    let mut state = chip(&rom);
+    state.set_keymap(keymap);
+    run_with(&mut state, 2, &remapped);
+    assert_eq!(state.pc(), 0x206);
+
+    let mut unmapped = NO_KEYS;
+    unmapped[1] = true;
+    let mut state = chip(&rom);
+    state.set_keymap(keymap);
+    run_with(&mut state, 2, &unmapped);
+    assert_eq!(state.pc(), 0x204);
+}
+
+#[test]
+fn audio_pattern_and_pitch_accessors() {
+    let pattern: [u8; 16] = std::array::from_fn(|i| (i as u8) * 0x11);
+    let mut state = chip(&[0xA3, 0x00, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A]);
+    state.set_memory(0x300, &pattern);
+    assert_eq!(state.audio_pattern(), None);
+    assert_eq!(state.playback_frequency(), 4000.0);
+
+    run(&mut state, 2);
+    assert_eq!(state.audio_pattern(), Some(&pattern));
+
+    // Pitch 112 is 48 steps above the default of 64, an octave up
+    run(&mut state, 2);
+    assert!((state.playback_frequency() - 8000.0).abs() < 0.01);
+}
+
+#[test]
+fn headless_run_collects_changed_frames() {
+    // Draw the 0 glyph at the origin, clear, then spin
+    let mut state = chip(&[0xA0, 0x50, 0xD0, 0x05, 0x00, 0xE0, 0x12, 0x06]);
+    let mut polls = 0;
+    let frames = state.run_headless(10, || {
+        polls += 1;
+        NO_KEYS
+    });
+    assert_eq!(polls, 10);
+    assert_eq!(frames.len(), 2);
+    let row: Vec<bool> = frames[0][0][..8].iter().map(|&p| p.into()).collect();
+    assert_eq!(row, [true, true, true, true, false, false, false, false]);
+    assert!(frames[1].iter().flatten().all(|&p| !bool::from(p)));
+}
+
+#[test]
+fn loads_and_runs_at_0x600() {
+    let mut state = ChipState::new(700);
+    state.load_bytes(&[0x60, 0x07, 0x16, 0x00], 0x600).unwrap();
+    assert_eq!(state.pc(), 0x600);
+    assert_eq!(state.memory()[0x600..0x604], [0x60, 0x07, 0x16, 0x00]);
+    run(&mut state, 2);
+    assert_eq!(state.registers()[0], 0x07);
+    assert_eq!(state.pc(), 0x600);
+
+    // Reset returns to the load address rather than 0x200
+    run(&mut state, 1);
+    state.reset();
+    assert_eq!(state.pc(), 0x600);
+
+    assert!(matches!(
+        state.load_bytes(&[0; 0xA01], 0x600),
+        Err(Chip8Error::RomTooLarge {
+            size: 0xA01,
+            capacity: 0xA00
+        })
+    ));
+}
+
+#[test]
+fn cycle_count_skips_paused_steps() {
+    let mut state = chip(&[0x70, 0x01, 0x12, 0x00]);
+    assert_eq!(state.cycle_count(), 0);
+    run(&mut state, 10);
+    assert_eq!(state.cycle_count(), 10);
+    state.pause();
+    run(&mut state, 5);
+    assert_eq!(state.cycle_count(), 10);
+    state.resume();
+    assert!(state.run_cycles(7, &NO_KEYS).is_none());
+    assert_eq!(state.cycle_count(), 17);
+
+    // Steps waiting on Fx0A count, though nothing is fetched
+    let mut state = chip(&[0xF0, 0x0A, 0x12, 0x02]);
+    run(&mut state, 5);
+    assert_eq!(state.pc(), 0x202);
+    assert_eq!(state.cycle_count(), 5);
+}
+
+#[test]
+fn same_seed_gives_same_random_bytes() {
+    // Fill V0-VE with random bytes
+    let rom: Vec<u8> = (0..15).flat_map(|r| [0xC0 | r, 0xFF]).collect();
+    let randoms = |seed| {
+        let mut state = ChipState::with_seed(700, seed);
+        state.load_bytes(&rom, 0x200).unwrap();
+        run(&mut state, 15);
+        *state.registers()
+    };
+    assert_eq!(randoms(42), randoms(42));
+    assert_ne!(randoms(42), randoms(43));
+
+    let mut state = ChipState::new(700);
+    state.load_bytes(&rom, 0x200).unwrap();
+    state.reseed(42);
+    run(&mut state, 15);
+    assert_eq!(*state.registers(), randoms(42));
+}
+
+#[test]
+fn dirty_region_covers_changed_pixels() {
+    // Draw 5 zero bytes from 0x300, then the 0 glyph at (2, 3)
+    let mut state = chip(&[
+        0xA3, 0x00, 0xD0, 0x05, 0xA0, 0x50, 0x60, 0x02, 0x61, 0x03, 0xD0, 0x15,
+    ]);
+    state.take_dirty_region();
+    run(&mut state, 2);
+    assert_eq!(state.take_dirty_region(), None);
+    run(&mut state, 4);
+    assert_eq!(
+        state.take_dirty_region(),
+        Some(DisplayDiff {
+            x: 2,
+            y: 3,
+            width: 4,
+            height: 5
+        })
+    );
+    assert_eq!(state.take_dirty_region(), None);
+}
+
+#[test]
+fn clipped_sprite_vf_per_quirk() {
+    // Draw the 0 glyph twice at (0, y), so 2 rows are drawn and 3 clipped
+    let rom = |hires: bool, y: u8| {
+        let mut rom = if hires { vec![0x00, 0xFF] } else { vec![] };
+        rom.extend([0x60, 0x00, 0x61, y, 0xA0, 0x50, 0xD0, 0x15, 0xD0, 0x15]);
+        rom
+    };
+    let counting = Quirks {
+        sprite_clip_counts_collisions: true,
+        ..Default::default()
+    };
+    let hires_counting = Quirks {
+        hires_collision_count: true,
+        ..Default::default()
+    };
+    for (quirks, hires, y, vf) in [
+        (Quirks::default(), false, 30, [0, 1]),
+        (counting, false, 30, [3, 5]),
+        (hires_counting, false, 30, [0, 1]),
+        (hires_counting, true, 62, [3, 5]),
+    ] {
+        let mut state = chip_with(quirks, &rom(hires, y));
+        run(&mut state, 4 + hires as usize);
+        assert_eq!(state.registers()[0xF], vf[0], "{:?}", quirks);
+        run(&mut state, 1);
+        assert_eq!(state.registers()[0xF], vf[1], "{:?}", quirks);
+    }
+}
+
+#[test]
+fn trace_registers_logs_each_instruction() {
+    use std::sync::Mutex;
+    use std::thread::{self, ThreadId};
+
+    // Other tests log concurrently, so lines are kept per thread
+    struct Capture(Mutex<Vec<(ThreadId, String)>>);
+    impl log::Log for Capture {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() == log::Level::Trace
+        }
+        fn log(&self, record: &log::Record) {
+            let line = (thread::current().id(), record.args().to_string());
+            self.0.lock().unwrap().push(line);
+        }
+        fn flush(&self) {}
+    }
+    static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+    log::set_logger(&CAPTURE).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+    let captured = || -> Vec<String> {
+        let lines = CAPTURE.0.lock().unwrap();
+        let id = thread::current().id();
+        lines
+            .iter()
+            .filter(|l| l.0 == id)
+            .map(|l| l.1.clone())
+            .collect()
+    };
+
+    let mut state = chip(&[0x60, 0x05, 0xA1, 0x23, 0x71, 0x01]);
+    state.step(&NO_KEYS);
+    assert!(captured().is_empty());
+
+    state.set_trace_registers(true);
+    state.step(&NO_KEYS);
+    state.set_trace_registers(false);
+    state.step(&NO_KEYS);
+    assert_eq!(
+        captured(),
+        [
+            "pc=0x202 opcode=0xA123 LD I, 0x123 I=0x123 V=[05, 00, 00, 00, 00, 00, 00, 00, 00, \
+          00, 00, 00, 00, 00, 00, 00]"
+        ]
+    );
+}
+
+#[test]
+fn index_add_past_end_of_memory() {
+    // VF = 7, I = 0xFFE, then add 1 twice
+    let rom = [0x6F, 0x07, 0x60, 0x01, 0xAF, 0xFE, 0xF0, 0x1E, 0xF0, 0x1E];
+    let amiga = Quirks {
+        index_overflow_sets_vf: true,
+        ..Default::default()
+    };
+    let mut state = chip_with(amiga, &rom);
+    run(&mut state, 4);
+    assert_eq!((state.index(), state.registers()[0xF]), (0xFFF, 0));
+    run(&mut state, 1);
+    assert_eq!((state.index(), state.registers()[0xF]), (0x000, 1));
+
+    let mut state = chip(&rom);
+    run(&mut state, 5);
+    assert_eq!((state.index(), state.registers()[0xF]), (0x000, 7));
+}
+
+#[test]
+fn embedded_roms_load_and_run() {
+    for (name, bytes) in crate::roms::ROMS {
+        let mut state = ChipState::new(700);
+        state.load_embedded(name).unwrap();
+        let end = 0x200 + bytes.len();
+        assert_eq!(state.memory()[0x200..end], **bytes, "{}", name);
+        assert!(state.memory()[end..].iter().all(|&b| b == 0), "{}", name);
+        assert_eq!(state.rom_info().map(|info| info.len), Some(bytes.len()));
+
+        let frames = state.run_headless(2000, || NO_KEYS);
+        assert!(!frames.is_empty(), "{}", name);
+        assert!(!state.is_halted(), "{}", name);
+    }
+    assert!(matches!(
+        ChipState::new(700).load_embedded("missing"),
+        Err(Chip8Error::UnknownRom(name)) if name == "missing"
+    ));
+}
+
+#[test]
+fn diagnostics_show_illegal_opcode_and_history() {
+    // V0 = 5, 19 increments of V1, then an illegal 5xy1
+    let mut rom = vec![0x60, 0x05];
+    rom.extend([0x71, 0x01].repeat(19));
+    rom.extend([0x5A, 0xB1]);
+    let mut state = chip(&rom);
+    run(&mut state, 21);
+    assert!(state.is_halted());
+
+    let diagnostics = state.dump_diagnostics();
+    assert!(diagnostics.contains("0x228: 5AB1"), "{}", diagnostics);
+    assert!(diagnostics.contains("0x226: 7101"), "{}", diagnostics);
+    assert!(diagnostics.contains("V0-V7: [05, 13,"), "{}", diagnostics);
+    // Only the last 16 opcodes are kept
+    assert!(!diagnostics.contains("6005"), "{}", diagnostics);
+    assert_eq!(diagnostics.matches(": 7101").count(), 15);
+}
+
+#[test]
+fn loads_from_bytes() {
+    let mut state = ChipState::new(700);
+    state.load_bytes(&[0x60, 0x07], 0x200).unwrap();
+    assert_eq!(state.memory()[0x200..0x202], [0x60, 0x07]);
+    assert_eq!(state.pc(), 0x200);
+
+    assert!(state.load_bytes(&[0xAA; 0xE00], 0x200).is_ok());
+    assert!(matches!(
+        state.load_bytes(&[0xAA; 0xE01], 0x200),
+        Err(Chip8Error::RomTooLarge {
+            size: 0xE01,
+            capacity: 0xE00
+        })
+    ));
+    assert!(matches!(
+        state.load_bytes(&[], 0x200),
+        Err(Chip8Error::EmptyRom)
+    ));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn loads_from_a_reader() {
+    let mut state = ChipState::new(700);
+    state
+        .load_reader(&[0x60, 0x07, 0x12, 0x00][..], 0x300)
+        .unwrap();
+    assert_eq!(state.memory()[0x300..0x304], [0x60, 0x07, 0x12, 0x00]);
+    assert_eq!(state.pc(), 0x300);
+
+    let oversized = io::repeat(0xAA).take(0xE01);
+    assert!(matches!(
+        state.load_reader(oversized, 0x200),
+        Err(Chip8Error::RomTooLarge { size: 0xE01, .. })
+    ));
+    assert!(matches!(
+        state.load_reader(io::empty(), 0x200),
+        Err(Chip8Error::EmptyRom)
+    ));
+}
+
+#[test]
+fn render_rgb_maps_pixels_to_colours() {
+    const ON: [u8; 3] = [0xFF, 0xB0, 0x00];
+    const OFF: [u8; 3] = [0x10, 0x20, 0x30];
+    let rgba = |state: &ChipState, width: usize, x: usize, y: usize| {
+        let rgb = state.render_rgb(ON, OFF);
+        let i = (y * width + x) * 4;
+        [rgb[i], rgb[i + 1], rgb[i + 2], rgb[i + 3]]
+    };
+
+    // Plot one pixel at (3, 2) from a sprite byte at 0x300
+    let mut state = chip(&[0x60, 0x03, 0x61, 0x02, 0xA3, 0x00, 0xD0, 0x11, 0x00, 0xFF]);
+    state.set_memory(0x300, &[0x80]);
+    run(&mut state, 4);
+    assert_eq!(state.render_rgb(ON, OFF).len(), 64 * 32 * 4);
+    assert_eq!(rgba(&state, 64, 3, 2), [0xFF, 0xB0, 0x00, 0xFF]);
+    assert_eq!(rgba(&state, 64, 4, 2), [0x10, 0x20, 0x30, 0xFF]);
+    let lit = state
+        .render_rgb(ON, OFF)
+        .chunks(4)
+        .filter(|p| p[..3] == ON)
+        .count();
+    assert_eq!(lit, 1);
+
+    run(&mut state, 1);
+    assert_eq!(state.render_rgb(ON, OFF).len(), 128 * 64 * 4);
+    assert_eq!(rgba(&state, 128, 0, 0), [0x10, 0x20, 0x30, 0xFF]);
+}
+
+#[test]
+fn set_frequency_updates_speed_mid_run() {
+    // DT = 60, then spin
+    let mut state = chip(&[0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04]);
+    assert_eq!(state.speed(), Duration::from_secs_f64(1.0 / 700.0));
+    run(&mut state, 2 + 350);
+    assert!(state.delay_timer().abs_diff(30) <= 1);
+
+    // Each step now covers a tenth of the time, so the rest of the second takes 10x the steps
+    state.set_frequency(7000);
+    assert_eq!(state.frequency(), 7000);
+    assert_eq!(state.speed(), Duration::from_secs_f64(1.0 / 7000.0));
+    run(&mut state, 350);
+    assert!(state.delay_timer() >= 25);
+    run(&mut state, 3150);
+    assert!(state.delay_timer() <= 1);
+
+    state.set_frequency(0);
+    assert_eq!(state.frequency(), 700);
+}
+
+#[test]
+fn replay_drives_key_instructions() {
+    // Wait for a key into V3, then V4 = 2 unless it is held again; then count in V5
+    let rom = [0xF3, 0x0A, 0xE3, 0x9E, 0x64, 0x02, 0x75, 0x01, 0x12, 0x08];
+    let held = |key: usize| {
+        let mut keys = NO_KEYS;
+        keys[key] = true;
+        keys
+    };
+
+    let mut state = chip(&rom);
+    state.replay(&[(NO_KEYS, 2), (held(9), 2), (NO_KEYS, 1), (held(9), 3)]);
+    assert_eq!(state.registers()[3], 9);
+    assert_eq!(state.registers()[4], 0);
+    assert_eq!(state.registers()[5], 1);
+
+    let mut state = chip(&rom);
+    state.replay(&[(NO_KEYS, 2), (held(9), 2), (NO_KEYS, 1), (held(8), 3)]);
+    assert_eq!(state.registers()[3], 9);
+    assert_eq!(state.registers()[4], 2);
+    assert_eq!(state.registers()[5], 1);
+}
+
+#[test]
+fn protect_reserved_blocks_store_and_bcd() {
+    // V0 = 0xAA, I = 0x50 (the font), then Fx55 and Fx33
+    let rom = [0x60, 0xAA, 0xA0, 0x50, 0xF0, 0x55, 0xF0, 0x33];
+    let mut state = chip(&rom);
+    state.set_protect_reserved(true);
+    let reserved = state.memory()[..0x200].to_vec();
+    run(&mut state, 4);
+    assert_eq!(state.memory()[..0x200], reserved);
+    assert!(!state.is_halted());
+
+    let mut state = chip(&rom);
+    run(&mut state, 3);
+    assert_eq!(state.memory()[0x50], 0xAA);
+    run(&mut state, 1);
+    assert_eq!(state.memory()[0x50..0x53], [1, 7, 0]);
+}
+
+#[test]
+fn cls_skips_redraw_when_already_clear() {
+    let mut state = chip(&[0x00, 0xE0, 0xA0, 0x50, 0xD0, 0x05, 0x00, 0xE0, 0x00, 0xE0]);
+    assert!(state.step(&NO_KEYS).is_none());
+    run(&mut state, 2);
+    let frame = state
+        .step(&NO_KEYS)
+        .expect("clearing a drawn display redraws");
+    assert!(frame.iter().flatten().all(|&p| !bool::from(p)));
+    assert!(state.step(&NO_KEYS).is_none());
+
+    assert_eq!(state.execute(Instruction::Cls, &NO_KEYS).ok(), Some(false));
+    state.display[0][0][0] = on();
+    assert_eq!(state.execute(Instruction::Cls, &NO_KEYS).ok(), Some(true));
+    assert!(!lit(&state, 0, 0, 0));
+}
+
+#[test]
+fn draw_vf_reset_quirk() {
+    // VF = 7, draw the 0 glyph, then draw it again over itself
+    let rom = [0x6F, 0x07, 0xA0, 0x50, 0xD0, 0x05, 0xD0, 0x05];
+    let preserving = Quirks {
+        draw_vf_reset: false,
+        ..Default::default()
+    };
+    let mut state = chip_with(preserving, &rom);
+    run(&mut state, 3);
+    assert_eq!(state.registers()[0xF], 7);
+    run(&mut state, 1);
+    assert_eq!(state.registers()[0xF], 1);
+
+    let mut state = chip(&rom);
+    run(&mut state, 3);
+    assert_eq!(state.registers()[0xF], 0);
+}
+
+#[test]
+fn self_jump_halts_when_detected() {
+    let rom = [0x60, 0x01, 0x12, 0x02];
+    let mut state = chip(&rom);
+    state.set_detect_halt(true);
+    run(&mut state, 1);
+    assert!(!state.is_halted());
+    run(&mut state, 1);
+    assert!(state.is_halted());
+    assert_eq!(state.pc(), 0x202);
+    let cycles = state.cycle_count();
+    assert!(state.step(&NO_KEYS).is_none());
+    assert_eq!(state.cycle_count(), cycles);
+
+    // Busy-waiting ROMs keep running by default
+    let mut state = chip(&rom);
+    run(&mut state, 10);
+    assert!(!state.is_halted());
+    assert_eq!(state.cycle_count(), 10);
+}
+
+#[test]
+fn vip_timing_makes_draws_slower_than_loads() {
+    let draw = Instruction::Draw(0, 0, 5);
+    let load = Instruction::Setr(0, 1);
+    let mut state = ChipState::new(700);
+    assert_eq!(state.instruction_time(&draw), state.instruction_time(&load));
+    state.set_timing_model(TimingModel::Vip);
+    assert!(state.instruction_time(&draw) > state.instruction_time(&load));
+
+    // DT = 255, I = 0x50, then loop over the given instruction
+    let timer_after = |op: [u8; 2]| {
+        let mut state = chip(&[0x60, 0xFF, 0xF0, 0x15, 0xA0, 0x50, op[0], op[1], 0x12, 0x06]);
+        state.set_timing_model(TimingModel::Vip);
+        run(&mut state, 62);
+        state.delay_timer()
+    };
+    assert!(timer_after([0xD0, 0x05]) < timer_after([0x61, 0x05]));
+}
+
+#[test]
+fn bcd_digits_and_memory_edges() {
+    let mut state = chip(&[0x00, 0xE0]);
+    state.set_index(0x300);
+    for (value, digits) in [
+        (0, [0, 0, 0]),
+        (9, [0, 0, 9]),
+        (10, [0, 1, 0]),
+        (99, [0, 9, 9]),
+        (100, [1, 0, 0]),
+        (255, [2, 5, 5]),
+    ] {
+        state.set_memory(0x300, &[0xEE; 3]);
+        state.set_register(0, value);
+        state.execute(Instruction::Bcd(0), &NO_KEYS).unwrap();
+        assert_eq!(state.memory()[0x300..0x303], digits, "{}", value);
+    }
+
+    // Digits past 0xFFF wrap to the bottom of memory
+    state.set_register(0, 255);
+    for (index, wrapped) in [(0xFFD, 0), (0xFFE, 1), (0xFFF, 2)] {
+        state.set_memory(0xFFD, &[0xEE; 3]);
+        state.set_memory(0x000, &[0xEE; 2]);
+        state.set_index(index);
+        state.execute(Instruction::Bcd(0), &NO_KEYS).unwrap();
+        let written: Vec<u8> = (0..3)
+            .map(|i| state.memory()[(index + i) as usize & 0xFFF])
+            .collect();
+        assert_eq!(written, [2, 5, 5], "{:#05X}", index);
+        assert_eq!(
+            state.memory()[0x000..0x002]
+                .iter()
+                .filter(|&&b| b != 0xEE)
+                .count(),
+            wrapped
+        );
+    }
+
+    // Or all land on the last byte when addresses clamp
+    let mut state = chip_with(
+        Quirks {
+            clamp_addresses: true,
+            ..Default::default()
+        },
+        &[0x00, 0xE0],
+    );
+    state.set_index(0xFFF);
+    state.set_register(0, 255);
+    state.execute(Instruction::Bcd(0), &NO_KEYS).unwrap();
+    assert_eq!(state.memory()[0xFFF], 5);
+}
+
+#[test]
+fn lsb_first_sprites_are_mirrored() {
+    // Draw 0b1100_0101 at the origin
+    let row = |order: BitOrder| {
+        let quirks = Quirks {
+            sprite_bit_order: order,
+            ..Default::default()
+        };
+        let mut state = chip_with(quirks, &[0xA3, 0x00, 0xD0, 0x01]);
+        state.set_memory(0x300, &[0b1100_0101]);
+        run(&mut state, 2);
+        (0..8).map(|x| lit(&state, 0, x, 0)).collect::<Vec<_>>()
+    };
+    let msb_first = row(BitOrder::MsbFirst);
+    assert_eq!(
+        msb_first,
+        [true, true, false, false, false, true, false, true]
+    );
+    let mut mirrored = msb_first;
+    mirrored.reverse();
+    assert_eq!(row(BitOrder::LsbFirst), mirrored);
+}
+
+#[test]
+fn instructions_skip_undecodable_words() {
+    // A load, an illegal FFFF, F000 NNNN, a jump and a trailing odd byte
+    let mut state = chip(&[
+        0x60, 0x01, 0xFF, 0xFF, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x00, 0x00,
+    ]);
+    let decoded: Vec<_> = state.instructions().collect();
+    assert_eq!(
+        decoded,
+        [
+            (0x200, Instruction::Setr(0, 1)),
+            (0x204, Instruction::LoadILong(0x1234)),
+            (0x208, Instruction::Jmp(0x200)),
+        ]
+    );
+
+    // Self-modified code is decoded as it is now
+    state.set_memory(0x200, &[0x61, 0x02]);
+    assert_eq!(
+        state.instructions().next(),
+        Some((0x200, Instruction::Setr(1, 2)))
+    );
+}
+
+#[test]
+fn register_watchpoint_fires_on_change() {
+    // V3 = 5, V3 = 5 again, V4 = 1, V3 = 7
+    let mut state = chip(&[0x63, 0x05, 0x63, 0x05, 0x64, 0x01, 0x63, 0x07]);
+    state.watch_register(3);
+    assert!(matches!(
+        state.step_debug(&NO_KEYS),
+        StepOutcome::Watchpoint {
+            kind: WatchKind::Register(3),
+            old: 0,
+            new: 5
+        }
+    ));
+    assert!(matches!(
+        state.step_debug(&NO_KEYS),
+        StepOutcome::Executed(_)
+    ));
+    assert!(matches!(
+        state.step_debug(&NO_KEYS),
+        StepOutcome::Executed(_)
+    ));
+    assert!(matches!(
+        state.step_debug(&NO_KEYS),
+        StepOutcome::Watchpoint {
+            kind: WatchKind::Register(3),
+            old: 5,
+            new: 7
+        }
+    ));
+
+    state.remove_watchpoint(WatchKind::Register(3));
+    state.set_pc(0x200);
+    assert!(matches!(
+        state.step_debug(&NO_KEYS),
+        StepOutcome::Executed(_)
+    ));
+    assert_eq!(state.registers()[3], 5);
+}
+
+fn assert_error(e: Chip8Error, message: &str, has_source: bool) {
+    use std::error::Error;
+    assert_eq!(e.to_string(), message);
+    assert_eq!(e.source().is_some(), has_source, "{:?}", e);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn io_error() {
+    struct Broken;
+    impl Read for Broken {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "access denied",
+            ))
+        }
+    }
+    let e = ChipState::new(700).load_reader(Broken, 0x200).unwrap_err();
+    assert!(matches!(e, Chip8Error::Io(_)));
+    assert_error(e, "access denied", true);
+}
+
+#[test]
+fn empty_rom_error() {
+    let e = ChipState::new(700).load_bytes(&[], 0x200).unwrap_err();
+    assert!(matches!(e, Chip8Error::EmptyRom));
+    assert_error(e, "ROM is empty", false);
+}
+
+#[test]
+fn rom_too_large_error() {
+    let e = ChipState::new(700)
+        .load_bytes(&[0; 0xF00], 0x200)
+        .unwrap_err();
+    assert!(matches!(
+        e,
+        Chip8Error::RomTooLarge {
+            size: 0xF00,
+            capacity: 0xE00
+        }
+    ));
+    assert_error(
+        e,
+        "ROM is 3840 bytes but only 3584 bytes are available",
+        false,
+    );
+}
+
+#[test]
+fn illegal_opcode_error() {
+    let e = chip(&[0x5A, 0xB1]).try_step(&NO_KEYS).unwrap_err();
+    let Chip8Error::IllegalOpcode(decode) = e else {
+        panic!("{:?}", e);
+    };
+    assert_eq!(
+        decode,
+        DecodeError {
+            opcode: 0x5AB1,
+            pc: 0x200
+        }
+    );
+    assert_error(e, "Unsupported instruction 0x5AB1 at 0x200", true);
+}
+
+#[test]
+fn stack_overflow_error() {
+    let mut state = chip(&[0x22, 0x00]);
+    for _ in 0..16 {
+        state.try_step(&NO_KEYS).unwrap();
+    }
+    let e = state.try_step(&NO_KEYS).unwrap_err();
+    assert!(matches!(e, Chip8Error::StackOverflow { pc: 0x202 }));
+    assert_error(e, "Stack overflow at 0x202", false);
+}
+
+#[test]
+fn stack_underflow_error() {
+    let e = chip(&[0x00, 0xEE]).try_step(&NO_KEYS).unwrap_err();
+    assert!(matches!(e, Chip8Error::StackUnderflow { pc: 0x202 }));
+    assert_error(e, "Stack underflow at 0x202", false);
+}
+
+#[test]
+fn memory_out_of_bounds_error() {
+    let e = ChipState::new(700)
+        .load_bytes(&[0x00, 0xE0], 0x1000)
+        .unwrap_err();
+    assert!(matches!(e, Chip8Error::MemoryOutOfBounds { addr: 0x1000 }));
+    assert_error(e, "Address 0x1000 is outside of memory", false);
+}
+
+#[test]
+fn code_write_error() {
+    // I = 0x200, then store V0 over the instruction that set I
+    let mut state = chip(&[0xA2, 0x00, 0xF0, 0x55]);
+    state.set_code_write_policy(CodeWritePolicy::Error);
+    state.try_step(&NO_KEYS).unwrap();
+    let e = state.try_step(&NO_KEYS).unwrap_err();
+    assert!(matches!(
+        e,
+        Chip8Error::CodeWrite {
+            pc: 0x202,
+            addr: 0x200
+        }
+    ));
+    assert_error(
+        e,
+        "Write at 0x202 modifies code at 0x200 that has already run",
+        false,
+    );
+}
+
+#[test]
+fn font_out_of_range_error() {
+    let e = ChipState::new(700).set_font(&[0; 80], 0x1C0).unwrap_err();
+    assert!(matches!(e, Chip8Error::FontOutOfRange { offset: 0x1C0 }));
+    assert_error(e, "Font at 0x1C0 does not fit below 0x200", false);
+}
+
+#[test]
+fn logic_ops_reset_vf_with_quirk() {
+    let vip = Quirks {
+        logic_quirk: true,
+        ..Default::default()
+    };
+    // V0 = 0b1100, V1 = 0b1010, VF = 7, then the operation
+    for (op, result) in [(0x1, 0b1110), (0x2, 0b1000), (0x3, 0b0110)] {
+        let rom = [0x60, 0b1100, 0x61, 0b1010, 0x6F, 0x07, 0x80, 0x10 | op];
+        for (quirks, vf) in [(vip, 0), (Quirks::default(), 7)] {
+            let mut state = chip_with(quirks, &rom);
+            run(&mut state, 4);
+            assert_eq!(state.registers()[0], result, "8xy{}", op);
+            assert_eq!(state.registers()[0xF], vf, "8xy{} {:?}", op, quirks);
+        }
+    }
+}
+
+#[test]
+fn setters_prepare_a_store() {
+    let mut state = ChipState::new(700);
+    assert!(state.set_register(0, 0xAB));
+    assert!(state.set_register(1, 0xCD));
+    assert!(!state.set_register(16, 1));
+    assert!(state.set_index(0x300));
+    assert!(!state.set_index(0x1000));
+    assert!(state.set_memory(0x400, &[0xF1, 0x55]));
+    assert!(!state.set_memory(0xFFF, &[0, 0]));
+    assert!(state.set_pc(0x400));
+    assert!(!state.set_pc(0x1000));
+
+    // Rejected values leave the state untouched
+    assert_eq!(state.index(), 0x300);
+    assert_eq!(state.pc(), 0x400);
+    assert_eq!(state.memory()[0xFFF], 0);
+
+    run(&mut state, 1);
+    assert_eq!(state.memory()[0x300..0x303], [0xAB, 0xCD, 0x00]);
+}
+
+#[test]
+fn exec_hooks_observe_instructions() {
+    use std::sync::{Arc, Mutex};
+
+    let pre = Arc::new(Mutex::new(Vec::new()));
+    let post = Arc::new(Mutex::new(Vec::new()));
+    let mut state = chip(&[0x60, 0x05, 0x70, 0x01, 0x12, 0x00]);
+    let seen = pre.clone();
+    state.set_pre_exec_hook(Box::new(move |pc, instruction| {
+        seen.lock().unwrap().push((pc, *instruction));
+    }));
+    let seen = post.clone();
+    state.set_post_exec_hook(Box::new(move |pc, _, state| {
+        seen.lock().unwrap().push((pc, state.registers()[0]));
+    }));
+    run(&mut state, 3);
+    assert_eq!(
+        *pre.lock().unwrap(),
+        [
+            (0x200, Instruction::Setr(0, 5)),
+            (0x202, Instruction::Addr(0, 1)),
+            (0x204, Instruction::Jmp(0x200)),
+        ]
+    );
+    // The post-exec hook sees the state after the instruction ran
+    assert_eq!(*post.lock().unwrap(), [(0x200, 5), (0x202, 6), (0x204, 6)]);
+
+    state.clear_exec_hooks();
+    run(&mut state, 3);
+    assert_eq!(pre.lock().unwrap().len(), 3);
+    assert_eq!(post.lock().unwrap().len(), 3);
+}
+
+#[test]
+fn clones_share_no_state() {
+    // Count in V0, store it at I = 0, then loop
+    let mut state = chip(&[0x70, 0x01, 0xF0, 0x55, 0x12, 0x00]);
+    let mut clone = state.clone();
+    let worker = std::thread::spawn(move || {
+        run(&mut clone, 30);
+        (clone.registers()[0], clone.memory()[0])
+    });
+    run(&mut state, 3);
+    assert_eq!(worker.join().unwrap(), (10, 10));
+    assert_eq!(state.registers()[0], 1);
+    assert_eq!(state.memory()[0], 1);
+
+    // A seeded clone continues the same random sequence
+    let mut state = ChipState::with_seed(700, 7);
+    state.load_bytes(&[0xC0, 0xFF, 0x12, 0x00], 0x200).unwrap();
+    run(&mut state, 2);
+    let mut clone = state.clone();
+    run(&mut state, 1);
+    run(&mut clone, 1);
+    assert_eq!(state.registers()[0], clone.registers()[0]);
+}
+
+/// Splits a flat JSON object into its keys and raw values.
+#[cfg(feature = "trace_json")]
+fn json_fields(line: &str) -> std::collections::HashMap<String, String> {
+    let body = line.strip_prefix('{').unwrap().strip_suffix('}').unwrap();
+    let (mut fields, mut field) = (Vec::new(), String::new());
+    let (mut depth, mut quoted) = (0, false);
+    for c in body.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '[' if !quoted => depth += 1,
+            ']' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                fields.push(std::mem::take(&mut field));
+                continue;
+            }
+            _ => (),
+        }
+        field.push(c);
+    }
+    fields.push(field);
+    fields
+        .iter()
+        .map(|field| {
+            let (key, value) = field.split_once(':').unwrap();
+            (key.trim_matches('"').to_string(), value.to_string())
+        })
+        .collect()
+}
+
+#[cfg(feature = "trace_json")]
+#[test]
+fn json_trace_parses_back() {
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+    impl io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let out = Shared(Arc::new(Mutex::new(Vec::new())));
+    let mut state = chip(&[0x60, 0x05, 0xA1, 0x23, 0x71, 0xFF]);
+    state.set_trace_json(Box::new(out.clone()));
+    run(&mut state, 3);
+    state.pause();
+    run(&mut state, 2);
+
+    let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<_> = text.lines().map(json_fields).collect();
+    assert_eq!(lines.len(), 3);
+    let expected = [
+        (0x200, 0x6005, "LD V0, 0x05", [5, 0], 0x000),
+        (0x202, 0xA123, "LD I, 0x123", [5, 0], 0x123),
+        (0x204, 0x71FF, "ADD V1, 0xFF", [5, 0xFF], 0x123),
+    ];
+    for (fields, (pc, opcode, mnemonic, registers, index)) in lines.iter().zip(expected) {
+        assert_eq!(fields["pc"].parse::<u16>(), Ok(pc));
+        assert_eq!(fields["opcode"].parse::<u16>(), Ok(opcode));
+        assert_eq!(fields["mnemonic"], format!("\"{}\"", mnemonic));
+        assert_eq!(fields["index"].parse::<u16>(), Ok(index));
+        let values: Vec<u8> = fields["registers"]
+            .trim_matches(|c| c == '[' || c == ']')
+            .split(", ")
+            .map(|v| v.parse().unwrap())
+            .collect();
+        assert_eq!(values.len(), 16);
+        assert_eq!(values[..2], registers);
+    }
+}
+
+#[test]
+fn add_byte_to_vf_keeps_the_sum() {
+    // VF += kk wraps to 0x01 either way, with no carry flag written over the sum
+    for platform in [
+        Platform::Chip8,
+        Platform::Vip,
+        Platform::SuperChip,
+        Platform::XoChip,
+    ] {
+        for rom in [[0x6F, 0x02, 0x7F, 0xFF], [0x6F, 0xFF, 0x7F, 0x02]] {
+            let mut state = chip_with(platform.quirks(), &rom);
+            run(&mut state, 2);
+            assert_eq!(state.registers()[0xF], 0x01, "{:?} {:02X?}", platform, rom);
+        }
+    }
+}
+
+#[test]
+fn reset_policy_decides_flag_persistence() {
+    // V0-V1 = 9, 8, save them to the flags, then DT = 9
+    let rom = [0x60, 0x09, 0x61, 0x08, 0xF1, 0x75, 0xF0, 0x15];
+    for (policy, flags) in [
+        (ResetPolicy::Soft, [9, 8, 0, 0, 0, 0, 0, 0]),
+        (ResetPolicy::Hard, [0; 8]),
+    ] {
+        let mut state = chip(&rom);
+        run(&mut state, 4);
+        assert_eq!(state.flags()[..2], [9, 8]);
+
+        state.reset_with(policy);
+        assert_eq!(*state.flags(), flags, "{:?}", policy);
+        assert_eq!(*state.registers(), [0; 16]);
+        assert_eq!(state.delay_timer(), 0);
+        assert_eq!(state.pc(), 0x200);
+    }
+
+    // reset is a soft reset
+    let mut state = chip(&rom);
+    run(&mut state, 3);
+    state.reset();
+    assert_eq!(state.flags()[..2], [9, 8]);
+}
+
+#[test]
+fn hires_origin_wraps_at_128x64() {
+    // Hires, then plot one pixel at (V0, V1)
+    let plot = |x: u8, y: u8| {
+        let mut state = chip(&[0x00, 0xFF, 0x60, x, 0x61, y, 0xA3, 0x00, 0xD0, 0x11]);
+        state.set_memory(0x300, &[0x80]);
+        run(&mut state, 5);
+        (0..64)
+            .flat_map(|y| (0..128).map(move |x| (x, y)))
+            .filter(|&(x, y)| lit(&state, 0, x, y))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(plot(100, 40), [(100, 40)]);
+    assert_eq!(plot(127, 63), [(127, 63)]);
+    assert_eq!(plot(200, 70), [(72, 6)]);
+    assert_eq!(plot(128, 64), [(0, 0)]);
+}
+
+#[test]
+fn cycle_locked_timers_replay_exactly() {
+    // DT = random byte, then spin
+    let timers = || {
+        let mut state = ChipState::with_seed(600, 3);
+        state.set_timer_mode(TimerMode::CycleLocked);
+        state
+            .load_bytes(&[0xC0, 0xFF, 0xF0, 0x15, 0x12, 0x04], 0x200)
+            .unwrap();
+        (0..300)
+            .map(|_| {
+                state.step(&NO_KEYS);
+                (state.registers()[0], state.delay_timer())
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(timers(), timers());
+
+    // 600Hz / 60Hz ticks once every 10 steps
+    let mut state = ChipState::new(600);
+    state.set_timer_mode(TimerMode::CycleLocked);
+    state
+        .load_bytes(&[0x60, 0x05, 0xF0, 0x15, 0x12, 0x04], 0x200)
+        .unwrap();
+    let mut ticks = Vec::new();
+    for step in 1..=40 {
+        let before = state.delay_timer();
+        state.step(&NO_KEYS);
+        if state.delay_timer() < before {
+            ticks.push(step);
+        }
+    }
+    assert_eq!(ticks, [10, 20, 30, 40]);
+}
+
+#[test]
+fn register_ranges_save_and_load_in_order() {
+    let mut state = chip(&[
+        0x62, 0x22, 0x63, 0x33, 0x64, 0x44, // V2-V4 = 22, 33, 44
+        0xA3, 0x00, 0x52, 0x42, // save V2..V4 at 0x300
+        0xA3, 0x10, 0x54, 0x22, // save V4..V2 at 0x310
+        0xA3, 0x00, 0x57, 0x53, // load V7..V5 from 0x300
+        0xA3, 0x10, 0x58, 0xA3, // load V8..VA from 0x310
+    ]);
+    run(&mut state, 7);
+    assert_eq!(state.memory()[0x300..0x303], [0x22, 0x33, 0x44]);
+    assert_eq!(state.memory()[0x310..0x313], [0x44, 0x33, 0x22]);
+    run(&mut state, 4);
+    assert_eq!(state.registers()[5..8], [0x44, 0x33, 0x22]);
+    assert_eq!(state.registers()[8..11], [0x44, 0x33, 0x22]);
+    // Unlike Fx55 and Fx65, I is left alone
+    assert_eq!(state.index(), 0x310);
+}
+
+#[test]
+fn hexdump_shows_the_font() {
+    let state = ChipState::new(700);
+    let dump = state.hexdump(0x50, 80);
+    let lines: Vec<_> = dump.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(
+        lines[0],
+        "0x050: F0 90 90 90 F0 20 60 20 20 70 F0 10 F0 80 F0 F0 |..... `  p......|"
+    );
+
+    // Every byte of the font is listed, 16 to a line
+    for (i, line) in lines.iter().enumerate() {
+        assert!(line.starts_with(&format!("{:#05X}: ", 0x50 + i * 16)));
+        let hex: Vec<u8> = line[7..54]
+            .split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+            .collect();
+        assert_eq!(hex, font::FONT[i * 16..(i + 1) * 16]);
+    }
+
+    // Dumps are clamped to memory
+    assert_eq!(
+        state.hexdump(0xFFE, 10),
+        "0xFFE: 00 00                                           |..|\n"
+    );
+    assert_eq!(state.hexdump(0x1000, 10), "");
+}
+
+#[test]
+fn shift_in_place_keeps_result_and_flag() {
+    for shift_uses_vy in [false, true] {
+        let quirks = Quirks {
+            shift_uses_vy,
+            ..Default::default()
+        };
+        for (value, op, result, vf) in [
+            (0x81, 0x16, 0x40, 1),
+            (0x7E, 0x16, 0x3F, 0),
+            (0x81, 0x1E, 0x02, 1),
+            (0x7E, 0x1E, 0xFC, 0),
+        ] {
+            let mut state = chip_with(quirks, &[0x61, value, 0x81, op]);
+            run(&mut state, 2);
+            let context = format!("81{:02X} on {:#04X}, {:?}", op, value, quirks);
+            assert_eq!(state.registers()[1], result, "{}", context);
+            assert_eq!(state.registers()[0xF], vf, "{}", context);
+        }
+    }
+}
+
+#[test]
+fn step_compute_matches_step() {
+    // Draw each digit's glyph at (V0, V0) while counting, with a random byte in V2
+    let mut stepped = ChipState::with_seed(700, 11);
+    stepped
+        .load_bytes(
+            &[0xF0, 0x29, 0xD0, 0x05, 0xC2, 0xFF, 0x70, 0x01, 0x12, 0x00],
+            0x200,
+        )
+        .unwrap();
+    let mut computed = stepped.clone();
+    for _ in 0..400 {
+        stepped.step(&NO_KEYS);
+        computed.step_compute(&NO_KEYS);
+    }
+    assert_eq!(stepped.save_state(), computed.save_state());
+    assert_eq!(stepped.display_hash(), computed.display_hash());
+    assert_eq!(stepped.cycle_count(), computed.cycle_count());
+}
+
+#[test]
+fn display_by_reference_matches_copies() {
+    let bits =
+        |frame: &Display| -> Vec<bool> { frame.iter().flatten().map(|&p| p.into()).collect() };
+    // Draw each digit at (V0, V0) while counting, switching to hires on the way
+    let mut copied = chip(&[0xF0, 0x29, 0xD0, 0x05, 0x70, 0x01, 0x00, 0xFF, 0x12, 0x00]);
+    let mut by_ref = copied.clone();
+    let mut last = None;
+    for _ in 0..300 {
+        let frame = copied.step(&NO_KEYS);
+        assert_eq!(by_ref.step_in_place(&NO_KEYS), frame.is_some());
+        last = frame.or(last);
+        if let Some(frame) = &last {
+            assert_eq!(bits(frame), bits(by_ref.display()));
+        }
+    }
+    assert!(last.is_some());
+
+    // A step that builds frames catches up after step_compute
+    let mut computed = copied.clone();
+    for _ in 0..5 {
+        copied.step(&NO_KEYS);
+        computed.step_compute(&NO_KEYS);
+    }
+    copied.step(&NO_KEYS);
+    computed.step_in_place(&NO_KEYS);
+    assert_eq!(bits(copied.display()), bits(computed.display()));
+}
+
+#[test]
+fn halts_on_zero_after_running_off_the_rom() {
+    // Two instructions and a SYS call, followed by zeroed memory
+    let rom = [0x60, 0x01, 0x70, 0x01, 0x01, 0x23];
+    let mut halting = chip(&rom);
+    halting.set_halt_on_zero(true);
+    let mut spinning = halting.clone();
+    spinning.set_halt_on_zero(false);
+    run(&mut halting, 10);
+    run(&mut spinning, 10);
+
+    // Other 0nnn opcodes still run as a Nop
+    assert!(halting.is_halted());
+    assert_eq!(halting.pc(), 0x206);
+    assert_eq!(halting.registers()[0], 2);
+    assert_eq!(halting.cycle_count(), 4);
+    assert!(!spinning.is_halted());
+    assert_eq!(spinning.pc(), 0x214);
+}
+
+#[test]
+fn downscale_modes_reduce_2x2_blocks() {
+    // Hires, draw rows .x, xx, x., .. at the origin; the first block has 3 of 4 lit and
+    // the one below it 1 of 4
+    let rom = [
+        0x00, 0xFF, 0xA2, 0x08, 0xD0, 0x04, 0x12, 0x06, 0x40, 0xC0, 0x80, 0x00,
+    ];
+    for (mode, top, below) in [
+        (DownscaleMode::Nearest, false, true),
+        (DownscaleMode::OrSample, true, true),
+        (DownscaleMode::AverageThreshold, true, false),
+    ] {
+        let mut state = chip(&rom);
+        state.set_downscale_mode(mode);
+        run(&mut state, 4);
+        let frame = state.display();
+        assert_eq!(bool::from(frame[0][0]), top, "{:?}", mode);
+        assert_eq!(bool::from(frame[1][0]), below, "{:?}", mode);
+        assert!(!bool::from(frame[0][1]), "{:?}", mode);
+    }
+}
+
+#[test]
+fn input_state_snapshots() {
+    let mut input = InputState::new();
+    assert_eq!(input.snapshot(), NO_KEYS);
+    assert!(input.press(0x3));
+    assert!(input.press(0xF));
+    assert!(!input.press(0x10));
+    let held = input.snapshot();
+    assert!(held[0x3] && held[0xF]);
+    assert_eq!(held.iter().filter(|&&down| down).count(), 2);
+
+    assert!(input.release(0x3));
+    assert!(!input.release(0x10));
+    let after = input.snapshot();
+    assert!(!after[0x3] && after[0xF]);
+    assert!(input.is_down(0xF) && !input.is_down(0x3) && !input.is_down(200));
+    // Snapshots are copies, unaffected by later changes
+    assert!(held[0x3]);
+
+    input.release_all();
+    assert_eq!(input.snapshot(), NO_KEYS);
+    assert_eq!(InputState::from(after).snapshot(), after);
+}
+
+#[test]
+fn display_wait_defers_second_draw() {
+    // I = glyph 0, draw at (0, 0), draw at (8, 0), V2 += 1, spin
+    let rom = [
+        0xF0, 0x29, 0x61, 0x08, 0xD0, 0x05, 0xD1, 0x05, 0x72, 0x01, 0x12, 0x0A,
+    ];
+    let quirks = Quirks {
+        display_wait: true,
+        ..Default::default()
+    };
+    let mut state = chip_with(quirks, &rom);
+    assert!(state.step_frame(20, &NO_KEYS).is_some());
+    assert_eq!(state.cycle_count(), 4);
+    assert_eq!(state.pc(), 0x206);
+    assert!(lit(&state, 0, 0, 0));
+    assert!(!lit(&state, 0, 8, 0));
+
+    assert!(state.step_frame(20, &NO_KEYS).is_some());
+    assert!(lit(&state, 0, 8, 0));
+    assert_eq!(state.registers()[2], 1);
+
+    // Without the quirk both draws land in the first frame
+    let mut state = chip(&rom);
+    state.step_frame(20, &NO_KEYS);
+    assert!(lit(&state, 0, 0, 0) && lit(&state, 0, 8, 0));
+}
+
+#[test]
+fn rom_info_reports_length_and_crc() {
+    // The standard CRC-32 check value
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(b""), 0);
+
+    let mut state = ChipState::new(700);
+    assert!(state.rom_info().is_none());
+    state.load_bytes(b"123456789", 0x300).unwrap();
+    let info = state.rom_info().unwrap();
+    assert_eq!(info.len, 9);
+    assert_eq!(info.crc32, 0xCBF4_3926);
+    assert_eq!((info.start, info.end), (0x300, 0x308));
+
+    // The checksum is of the ROM as loaded, not as it was later modified
+    state.set_memory(0x300, b"X");
+    assert_eq!(state.rom_info().unwrap().crc32, 0xCBF4_3926);
+}
+
+#[test]
+fn jump_with_offset_past_0xfff() {
+    // V0 = 0x20, then jump to 0xFF0 + V0
+    let rom = [0x60, 0x20, 0xBF, 0xF0];
+    for (extended_memory, pc) in [(true, 0x1010), (false, 0x010)] {
+        let quirks = Quirks {
+            extended_memory,
+            ..Default::default()
+        };
+        let mut state = chip_with(quirks, &rom);
+        run(&mut state, 2);
+        assert_eq!(state.pc(), pc, "{:?}", quirks);
+    }
+
+    // Bxnn adds Vx instead, here VF = 0x30
+    let quirks = Quirks {
+        extended_memory: true,
+        jump_uses_vx: true,
+        ..Default::default()
+    };
+    let mut state = chip_with(quirks, &[0x6F, 0x30, 0xBF, 0xF0]);
+    run(&mut state, 2);
+    assert_eq!(state.pc(), 0x1020);
+}
+
+#[test]
+fn font_index_masks_high_nibble() {
+    for (value, digit) in [(0x1A, 0xA), (0x0A, 0xA), (0xF3, 0x3), (0x10, 0x0)] {
+        let mut state = chip(&[0x63, value, 0xF3, 0x29]);
+        run(&mut state, 2);
+        assert_eq!(state.index(), 0x50 + 5 * digit, "{:#04X}", value);
+    }
+}
+
+#[test]
+fn sys_call_per_policy() {
+    // SYS 0x123, then V0 = 5
+    let rom = [0x01, 0x23, 0x60, 0x05];
+    for (policy, last_sys, halted, v0) in [
+        (SysPolicy::Ignore, None, false, 5),
+        (SysPolicy::Trap, Some(0x123), false, 5),
+        (SysPolicy::Halt, Some(0x123), true, 0),
+    ] {
+        let mut state = chip(&rom);
+        state.set_sys_policy(policy);
+        assert_eq!(state.last_sys(), None);
+        run(&mut state, 2);
+        assert_eq!(state.last_sys(), last_sys, "{:?}", policy);
+        assert_eq!(state.is_halted(), halted, "{:?}", policy);
+        assert_eq!(state.registers()[0], v0, "{:?}", policy);
+        if halted {
+            assert_eq!(state.pc(), 0x200);
+        }
+    }
+}
+
+#[test]
+fn store_into_code_warns() {
+    // V0 = 0xAA, I = 0x300, store (data, not code); I = 0x200, store over the first load
+    let rom = [0x60, 0xAA, 0xA3, 0x00, 0xF0, 0x55, 0xA2, 0x00, 0xF0, 0x55];
+    let mut state = chip(&rom);
+    state.set_code_write_policy(CodeWritePolicy::Warn);
+    run(&mut state, 3);
+    assert_eq!(state.memory()[0x300], 0xAA);
+    assert_eq!(state.last_code_write(), None);
+
+    run(&mut state, 2);
+    assert_eq!(state.last_code_write(), Some(0x200));
+    assert_eq!(state.memory()[0x200], 0xAA);
+    assert!(!state.is_halted());
+
+    // Nothing is recorded when writes aren't checked
+    let mut state = chip(&rom);
+    run(&mut state, 5);
+    assert_eq!(state.memory()[0x200], 0xAA);
+    assert_eq!(state.last_code_write(), None);
+}
+
+#[test]
+fn breakpoint_on_deferred_draw_stops_once() {
+    // I = glyph 0, draw, draw again (deferred to the next frame), then loop to the second
+    let quirks = Quirks {
+        display_wait: true,
+        ..Default::default()
+    };
+    let mut state = chip_with(quirks, &[0xA0, 0x50, 0xD0, 0x05, 0xD0, 0x05, 0x12, 0x04]);
+    state.add_breakpoint(0x204);
+    run(&mut state, 2);
+    assert!(matches!(
+        state.step_debug(&NO_KEYS),
+        StepOutcome::Breakpoint(0x204)
+    ));
+
+    // Resuming once retries the draw until the vertical blank without stopping again
+    let mut steps = 0;
+    while state.pc() == 0x204 {
+        assert!(matches!(
+            state.step_debug(&NO_KEYS),
+            StepOutcome::Executed(_)
+        ));
+        steps += 1;
+        assert!(steps < 50, "the deferred draw never ran");
+    }
+    assert!(steps > 1);
+    assert!(!lit(&state, 0, 0, 0));
+
+    // Arriving at it again is a new stop
+    run(&mut state, 1);
+    assert!(matches!(
+        state.step_debug(&NO_KEYS),
+        StepOutcome::Breakpoint(0x204)
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trips_default_and_xo_chip_states() {
+    // Default 4K state with a call on the stack
+    let mut state = chip(&[0x60, 0x05, 0x22, 0x06, 0x00, 0x00, 0x71, 0x02, 0x12, 0x08]);
+    run(&mut state, 3);
+    let json = serde_json::to_vec(&state).unwrap();
+    let restored: ChipState = serde_json::from_slice(&json).unwrap();
+    assert_eq!(restored.save_state(), state.save_state());
+    assert_eq!(restored.stack(), [0x204]);
+
+    // 64K XO-CHIP state with a deeper stack, writing past 0xFFF
+    let mut state = chip_with(
+        Platform::XoChip.quirks(),
+        &[0x60, 0xAB, 0xF0, 0x00, 0x12, 0x34, 0xF0, 0x55, 0x12, 0x08],
+    );
+    assert!(state.set_stack_depth(32));
+    run(&mut state, 4);
+    assert_eq!(state.memory()[0x1234], 0xAB);
+    let json = serde_json::to_vec(&state).unwrap();
+    let restored: ChipState = serde_json::from_slice(&json).unwrap();
+    assert_eq!(restored.save_state(), state.save_state());
+    assert_eq!(restored.memory()[0x1234], 0xAB);
+
+    assert!(serde_json::from_slice::<ChipState>(&json[..json.len() / 2]).is_err());
+}
+
+#[test]
+fn step_returns_frame_when_watchpoint_fires() {
+    // VF goes from 5 to 0 on a draw without collision
+    let mut state = chip(&[0x6F, 0x05, 0xA0, 0x50, 0xD0, 0x05]);
+    run(&mut state, 2);
+    state.watch_register(0xF);
+    let frame = state
+        .step(&NO_KEYS)
+        .expect("the draw should return a frame");
+    assert_eq!(state.registers[0xF], 0);
+    assert!(bool::from(frame[0][0]));
+}
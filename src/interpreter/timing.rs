@@ -0,0 +1,68 @@
+use super::Instruction;
+use core::time::Duration;
+
+/// One COSMAC VIP machine cycle: 8 clocks of the 1.76MHz CDP1802
+pub const VIP_CYCLE: Duration = Duration::from_nanos(4545);
+
+/// How much emulated time each instruction takes, which drives the 60Hz timers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimingModel {
+    /// Every instruction takes one period of the clock frequency
+    #[default]
+    Flat,
+    /// Each instruction takes its `cycle_cost` in VIP machine cycles
+    Vip,
+}
+
+/// What drives the delay and sound timers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimerMode {
+    /// The timers tick once per timer period of emulated time, per the `TimingModel`
+    #[default]
+    Duration,
+    /// The timers tick once every fixed number of steps, the clock frequency divided by
+    /// the timer frequency, so replays tick on exactly the same steps
+    CycleLocked,
+}
+
+/// Approximate number of COSMAC VIP machine cycles taken to execute `instruction`.
+///
+/// The costs are averages of the original interpreter's routines, including fetch and
+/// decode. Dxyn grows with the sprite height and Fx55/Fx65 with the number of registers.
+/// Instructions the VIP didn't have are costed like their closest VIP equivalent.
+pub fn cycle_cost(instruction: &Instruction) -> u32 {
+    use Instruction::*;
+
+    match *instruction {
+        Nop => 40,
+        Cls => 3078,
+        ScrollDown(n) => 200 + 100 * n as u32,
+        ScrollRight | ScrollLeft => 1600,
+        Lores | Hires => 3078,
+        Ret => 10,
+        Jmp(_) => 12,
+        Call(_) => 26,
+        Ske(..) | Skne(..) => 10,
+        Skre(..) | Skrne(..) => 14,
+        SaveRange(x, y) | LoadRange(x, y) => 14 + 14 * (x.abs_diff(y) as u32 + 1),
+        Setr(..) => 6,
+        Addr(..) => 10,
+        Move(..) | Or(..) | And(..) | Xor(..) => 44,
+        Add(..) | Sub(..) | Shr(..) | Ssub(..) | Shl(..) => 44,
+        Seti(_) | LoadILong(_) => 12,
+        Jmpr(..) => 22,
+        Rand(..) => 36,
+        Draw(_, _, n) => 68 + 68 * n.max(1) as u32,
+        Skp(_) | Sknp(_) => 14,
+        Moved(_) => 10,
+        Key(_) => 18,
+        Setrd(_) | Setrs(_) => 10,
+        Addi(_) => 16,
+        Ldfnt(_) | Ldbigfnt(_) => 20,
+        Bcd(_) => 84,
+        Store(x) | Load(x) => 14 + 14 * (x as u32 + 1),
+        StoreFlags(x) | LoadFlags(x) => 14 + 14 * (x.min(7) as u32 + 1),
+        StoreAudio => 14 + 14 * 16,
+        Plane(_) | SetPitch(_) => 10,
+    }
+}
@@ -0,0 +1,52 @@
+use super::Instruction;
+use chip8_base::Display;
+
+/// What happened during a single call to `ChipState::step_with_trace`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// Program counter before the step
+    pub pc: u16,
+    /// Raw opcode fetched from `pc`, or 0 if nothing was fetched
+    pub opcode: u16,
+    /// The instruction executed, or `None` if the interpreter was halted, paused or
+    /// could not decode the opcode
+    pub instruction: Option<Instruction>,
+}
+
+#[cfg(feature = "trace_json")]
+impl TraceRecord {
+    /// Formats the record, with the registers and I after it executed, as a line of JSON
+    /// (without the trailing newline).
+    pub fn to_json(&self, registers: &[u8; 16], index: u16) -> String {
+        // Mnemonics are plain ASCII, so Debug formatting quotes them as valid JSON
+        let mnemonic = match self.instruction {
+            Some(instruction) => format!("{:?}", instruction.to_string()),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"pc\":{},\"opcode\":{},\"mnemonic\":{},\"registers\":{:?},\"index\":{}}}",
+            self.pc, self.opcode, mnemonic, registers, index
+        )
+    }
+}
+
+/// The result of a single call to `ChipState::step_debug`.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum StepOutcome {
+    /// The step ran as normal, returning the display if it changed
+    Executed(Option<Display>),
+    /// Execution stopped before the instruction at this address
+    Breakpoint(u16),
+    /// The instruction just executed changed a watched register or memory address
+    Watchpoint { kind: WatchKind, old: u8, new: u8 },
+}
+
+/// Something a watchpoint is set on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WatchKind {
+    /// A register V0-VF
+    Register(u8),
+    /// A byte of memory
+    Memory(u16),
+}
@@ -0,0 +1,9 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod asm;
+pub mod disasm;
+pub mod interpreter;
+pub mod render;
+pub mod roms;
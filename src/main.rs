@@ -1,28 +1,166 @@
-mod interpreter;
-
+use chip8::interpreter::Platform;
+use chip8::{disasm, interpreter, render, roms};
 use clap::Parser;
-use env_logger;
+use std::io::{self, BufRead, Write};
 use std::{error::Error, path::PathBuf};
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
     env_logger::init();
     let args = Cli::parse();
 
-    let mut chip = interpreter::ChipState::new(args.freq);
-    chip.load(PathBuf::from(&args.rom))?;
+    if let Err(e) = run(args) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Cli) -> Result<(), Box<dyn Error>> {
+    if args.disasm {
+        let (bytes, start) = match &args.builtin {
+            Some(name) => (
+                roms::find(name).ok_or("Unknown built-in ROM.")?.to_vec(),
+                0x200,
+            ),
+            None => (std::fs::read(args.rom_path())?, args.start),
+        };
+        for (addr, opcode, text) in disasm::disassemble(&bytes, start) {
+            println!("{:#05X}  {:04X}  {}", addr, opcode, text);
+        }
+        return Ok(());
+    }
+
+    let mut chip = interpreter::ChipState::with_quirks(args.freq, args.variant.quirks());
+    match &args.builtin {
+        Some(name) => chip.load_embedded(name)?,
+        None => chip.load(args.rom_path(), args.start)?,
+    }
+    if let Some(info) = chip.rom_info() {
+        log::info!(
+            "Loaded {} bytes at {:#05X}-{:#05X}, CRC-32 {:08X}",
+            info.len,
+            info.start,
+            info.end,
+            info.crc32
+        );
+    }
+
+    #[cfg(feature = "trace_json")]
+    if let Some(path) = &args.trace {
+        chip.set_trace_json(Box::new(io::BufWriter::new(std::fs::File::create(path)?)));
+    }
+
+    if args.step {
+        return step_interactive(chip);
+    }
+
+    if let Some(path) = args.screenshot {
+        chip.run_cycles(args.screenshot_after, &[false; 16]);
+        chip.save_screenshot(path)?;
+        return Ok(());
+    }
 
     chip8_base::run(chip);
 }
 
+/// Executes one instruction per line read from stdin, printing what changed. A line of
+/// `k` followed by hex digits, e.g. `k 5 A`, holds those keys down for the following steps.
+fn step_interactive(mut chip: interpreter::ChipState) -> Result<(), Box<dyn Error>> {
+    println!("Press Enter to step, k and the keys to hold (e.g. k 5 A), or q to quit.");
+    let mut stdin = io::stdin().lock();
+    let mut line = String::new();
+    let mut keys = [false; 16];
+
+    while !chip.is_halted() {
+        print!("{:#05X}> ", chip.pc());
+        io::stdout().flush()?;
+        line.clear();
+        if stdin.read_line(&mut line)? == 0 || line.trim() == "q" {
+            break;
+        }
+        if let Some(held) = line.trim().strip_prefix('k') {
+            match parse_keys(held) {
+                Ok(held) => keys = held,
+                Err(e) => println!("{}", e),
+            }
+            continue;
+        }
+
+        let registers = *chip.registers();
+        let (display, trace) = chip.step_with_trace(&keys);
+        match trace.instruction {
+            Some(instruction) => println!("{:04X}  {}", trace.opcode, instruction),
+            None => println!("(waiting)"),
+        }
+        for (x, (before, after)) in registers.iter().zip(chip.registers()).enumerate() {
+            if before != after {
+                println!("  V{:X}: {:#04X} -> {:#04X}", x, before, after);
+            }
+        }
+        if let Some(display) = display {
+            print!("{}", render::to_ascii(&display));
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses whitespace separated hex digits into the keys to hold down. No digits releases
+/// every key.
+fn parse_keys(s: &str) -> Result<[bool; 16], &'static str> {
+    let mut keys = [false; 16];
+    for key in s.split_whitespace() {
+        match u8::from_str_radix(key, 16) {
+            Ok(key) if key < 16 => keys[key as usize] = true,
+            _ => return Err("Keys must be hex digits from 0 to F."),
+        }
+    }
+    Ok(keys)
+}
+
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
     /// A CHIP-8 ROM to load into the interpreter
-    #[clap(validator = rom_exists)]
-    rom: String,
+    #[clap(validator = rom_exists, required_unless_present = "builtin")]
+    rom: Option<String>,
     // Frequency to run the interpreter at
-    #[clap(action, default_value_t = 700)]
+    #[clap(action, default_value_t = 700, value_parser = freq_in_range)]
     freq: u32,
+    /// Address to load the ROM at and start executing from
+    #[clap(long, action, default_value = "0x200", value_parser = parse_address)]
+    start: u16,
+    /// Run one of the bundled ROMs (ibm, hex) instead of a file
+    #[clap(long, action, conflicts_with = "rom", value_parser = builtin_exists)]
+    builtin: Option<String>,
+    /// Platform whose quirks to run with: chip8 (modern), vip, chip48, schip or xochip
+    #[clap(long, action, default_value = "chip8", value_parser = parse_variant)]
+    variant: Platform,
+    /// Print a disassembly of the ROM instead of running it
+    #[clap(long, action)]
+    disasm: bool,
+    /// Step through the ROM one instruction at a time in the terminal instead of a window
+    #[clap(long, action, conflicts_with = "disasm")]
+    step: bool,
+    /// Run without a window and save the display to this file as a PBM image
+    #[clap(long, action, conflicts_with_all = &["disasm", "step"])]
+    screenshot: Option<PathBuf>,
+    /// Number of instructions to run before taking the screenshot
+    #[clap(long, action, default_value_t = 700, requires = "screenshot")]
+    screenshot_after: u64,
+    /// Write a newline-delimited JSON trace of every executed instruction to this file
+    #[cfg(feature = "trace_json")]
+    #[clap(long, action)]
+    trace: Option<PathBuf>,
+}
+
+impl Cli {
+    fn rom_path(&self) -> PathBuf {
+        PathBuf::from(
+            self.rom
+                .as_ref()
+                .expect("clap requires a ROM without --builtin"),
+        )
+    }
 }
 
 fn rom_exists(f: &str) -> Result<(), &'static str> {
@@ -33,3 +171,104 @@ fn rom_exists(f: &str) -> Result<(), &'static str> {
         Ok(())
     }
 }
+
+fn freq_in_range(f: &str) -> Result<u32, &'static str> {
+    let freq: u32 = f.parse().map_err(|_| "Frequency must be a whole number.")?;
+    if freq == 0 {
+        Err("Frequency must be above 0.")
+    } else {
+        if freq > 100_000 {
+            log::warn!("A frequency of {}Hz is likely too fast to be usable", freq);
+        }
+        Ok(freq)
+    }
+}
+
+fn parse_address(s: &str) -> Result<u16, &'static str> {
+    let addr = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    match addr {
+        Ok(addr) if addr <= 0x0FFF => Ok(addr),
+        _ => Err("Address must be between 0x000 and 0xFFF."),
+    }
+}
+
+fn parse_variant(name: &str) -> Result<Platform, &'static str> {
+    match name {
+        "chip8" => Ok(Platform::Chip8),
+        "vip" => Ok(Platform::Vip),
+        "chip48" => Ok(Platform::Chip48),
+        "schip" | "superchip" => Ok(Platform::SuperChip),
+        "xochip" => Ok(Platform::XoChip),
+        _ => Err("Variant must be one of chip8, vip, chip48, schip or xochip."),
+    }
+}
+
+fn builtin_exists(name: &str) -> Result<String, &'static str> {
+    match roms::find(name) {
+        Some(_) => Ok(name.to_string()),
+        None => Err("No built-in ROM with that name."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_keys_parse_as_hex_digits() {
+        let keys = parse_keys(" 5 a F").unwrap();
+        let held: Vec<usize> = (0..16).filter(|&key| keys[key]).collect();
+        assert_eq!(held, [0x5, 0xA, 0xF]);
+        assert_eq!(parse_keys(""), Ok([false; 16]));
+        assert!(parse_keys("10").is_err());
+        assert!(parse_keys("g").is_err());
+    }
+
+    #[test]
+    fn variants_map_to_platforms() {
+        for (name, platform) in [
+            ("chip8", Platform::Chip8),
+            ("vip", Platform::Vip),
+            ("chip48", Platform::Chip48),
+            ("schip", Platform::SuperChip),
+            ("superchip", Platform::SuperChip),
+            ("xochip", Platform::XoChip),
+        ] {
+            assert_eq!(parse_variant(name), Ok(platform));
+        }
+        assert!(parse_variant("Chip8").is_err());
+        assert!(parse_variant("").is_err());
+
+        let vip = parse_variant("vip").unwrap().quirks();
+        assert!(vip.shift_uses_vy && vip.display_wait && vip.logic_quirk);
+        assert!(!parse_variant("chip8").unwrap().quirks().shift_uses_vy);
+        assert!(
+            parse_variant("chip48")
+                .unwrap()
+                .quirks()
+                .memory_increment_by_x
+        );
+        assert!(
+            parse_variant("schip")
+                .unwrap()
+                .quirks()
+                .hires_collision_count
+        );
+        assert!(parse_variant("xochip").unwrap().quirks().extended_memory);
+    }
+
+    #[test]
+    fn frequencies_must_be_positive_whole_numbers() {
+        assert!(freq_in_range("0").is_err());
+        assert_eq!(freq_in_range("1"), Ok(1));
+        assert_eq!(freq_in_range("700"), Ok(700));
+        // Implausibly fast clocks only warn
+        assert_eq!(freq_in_range("4000000000"), Ok(4_000_000_000));
+        assert!(freq_in_range("5000000000").is_err());
+        assert!(freq_in_range("-1").is_err());
+        assert!(freq_in_range("7.5").is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use chip8_base::{Display, Pixel};
+
+/// Renders rows of pixels, such as a `Display`, as text, with `#` for lit pixels and a space
+/// for unlit ones. Each row ends with a newline.
+pub fn to_ascii<R: AsRef<[Pixel]>>(rows: &[R]) -> String {
+    let width = rows.first().map_or(0, |row| row.as_ref().len());
+    let mut text = String::with_capacity(rows.len() * (width + 1));
+    for row in rows {
+        for &pixel in row.as_ref() {
+            text.push(if pixel.into() { '#' } else { ' ' });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Encodes `display` as a binary (P4) PBM image, one image pixel per display pixel.
+/// PBM marks set bits as black, so lit pixels come out black on white.
+pub fn to_pbm(display: &Display) -> Vec<u8> {
+    let (width, height) = (display[0].len(), display.len());
+    let mut image = format!("P4\n{} {}\n", width, height).into_bytes();
+    for row in display {
+        // Rows are packed 8 pixels to a byte, most significant bit first
+        for chunk in row.chunks(8) {
+            let byte = chunk.iter().enumerate().fold(0u8, |byte, (i, &pixel)| {
+                byte | (bool::from(pixel) as u8) << (7 - i)
+            });
+            image.push(byte);
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_marks_lit_pixels() {
+        let mut display = [[Pixel::default(); 64]; 32];
+        for (x, y) in [(0, 0), (2, 0), (63, 1)] {
+            display[y][x] = Pixel::try_from(1).unwrap_or_default();
+        }
+        let text = to_ascii(&display);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 32);
+        assert_eq!(lines[0], format!("# #{}", " ".repeat(61)));
+        assert_eq!(lines[1], format!("{}#", " ".repeat(63)));
+        assert!(lines[2..].iter().all(|line| *line == " ".repeat(64)));
+        assert!(text.ends_with('\n'));
+    }
+
+    #[test]
+    fn pbm_packs_rows_msb_first() {
+        let mut display = [[Pixel::default(); 64]; 32];
+        for (x, y) in [(0, 0), (9, 0), (63, 31)] {
+            display[y][x] = Pixel::try_from(1).unwrap_or_default();
+        }
+        let image = to_pbm(&display);
+        let header = b"P4\n64 32\n";
+        assert_eq!(image[..header.len()], *header);
+        let pixels = &image[header.len()..];
+        assert_eq!(pixels.len(), 8 * 32);
+        assert_eq!(pixels[..2], [0x80, 0x40]);
+        assert_eq!(pixels[255], 0x01);
+        assert_eq!(pixels.iter().map(|b| b.count_ones()).sum::<u32>(), 3);
+    }
+}
@@ -0,0 +1,16 @@
+//! Small programs bundled with the interpreter so it can be tried without finding a ROM.
+//! All of them are written for this crate, load at 0x200 and run on any platform.
+
+/// Every built-in ROM, by name.
+pub const ROMS: &[(&str, &[u8])] = &[
+    // A striped IBM logo, in the spirit of the classic first test ROM
+    ("ibm", include_bytes!("ibm.ch8")),
+    // The hex digits 0-F from the built-in font, in two rows
+    ("hex", include_bytes!("hex.ch8")),
+];
+
+pub fn find(name: &str) -> Option<&'static [u8]> {
+    ROMS.iter()
+        .find(|(rom, _)| *rom == name)
+        .map(|(_, bytes)| *bytes)
+}